@@ -0,0 +1,195 @@
+//! The call graph of a module, built from `OpFunctionCall` and `OpEntryPoint`.
+use super::traversal::{DepthFirst, Event};
+use super::{PetSpirv, SpirvModule};
+use rspirv::dr::Operand;
+use spirv::Word;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// An `OpFunctionCall` instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CallSite {
+    /// The calling function
+    pub caller: Word,
+    /// The block of the caller that contains the call
+    pub block: Word,
+    pub callee: Word,
+}
+
+/// A function declared as an entry point by `OpEntryPoint`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EntryPoint {
+    pub function: Word,
+    pub name: String,
+    pub execution_model: spirv::ExecutionModel,
+}
+
+/// The calls between the functions of a module. It is built once per module
+/// and passed to the writers, which select the functions to draw with it.
+#[derive(Clone, Debug, Default)]
+pub struct CallGraph {
+    /// Every function of the module in module order
+    pub functions: Vec<Word>,
+    /// Every call in module order
+    pub calls: Vec<CallSite>,
+    pub entry_points: Vec<EntryPoint>,
+    callees: BTreeMap<Word, BTreeSet<Word>>,
+    recursion: Vec<Vec<Word>>,
+}
+
+impl CallGraph {
+    pub fn new(module: &SpirvModule) -> Self {
+        let mut functions = Vec::new();
+        let mut calls = Vec::new();
+        let mut callees: BTreeMap<Word, BTreeSet<Word>> = BTreeMap::new();
+        for f in &module.module.functions {
+            let pet = PetSpirv::new(module, f);
+            let caller = pet.function_id();
+            functions.push(caller);
+            callees.entry(caller).or_default();
+            for block in &f.blocks {
+                let block_id = match block.label.as_ref().and_then(|label| label.result_id) {
+                    Some(id) => id,
+                    None => continue,
+                };
+                for inst in &block.instructions {
+                    if inst.class.opcode != spirv::Op::FunctionCall {
+                        continue;
+                    }
+                    if let Some(&Operand::IdRef(callee)) = inst.operands.first() {
+                        calls.push(CallSite {
+                            caller,
+                            block: block_id,
+                            callee,
+                        });
+                        callees.entry(caller).or_default().insert(callee);
+                    }
+                }
+            }
+        }
+        let entry_points = module
+            .module
+            .entry_points
+            .iter()
+            .filter_map(|inst| match inst.operands.as_slice() {
+                [
+                    Operand::ExecutionModel(execution_model),
+                    Operand::IdRef(function),
+                    Operand::LiteralString(name),
+                    ..
+                ] => Some(EntryPoint {
+                    function: *function,
+                    name: name.clone(),
+                    execution_model: *execution_model,
+                }),
+                _ => None,
+            })
+            .collect();
+        let recursion = recursion(&functions, &callees);
+        CallGraph {
+            functions,
+            calls,
+            entry_points,
+            callees,
+            recursion,
+        }
+    }
+
+    /// The distinct functions called by `function`.
+    pub fn callees(&self, function: Word) -> Vec<Word> {
+        self.callees
+            .get(&function)
+            .map(|callees| callees.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// The distinct functions calling `function`.
+    pub fn callers(&self, function: Word) -> Vec<Word> {
+        self.callees
+            .iter()
+            .filter(|(_, callees)| callees.contains(&function))
+            .map(|(&caller, _)| caller)
+            .collect()
+    }
+
+    /// The entry points that start at `function`.
+    pub fn entry_points_of(&self, function: Word) -> Vec<&EntryPoint> {
+        self.entry_points
+            .iter()
+            .filter(|entry_point| entry_point.function == function)
+            .collect()
+    }
+
+    /// `function` and every function it calls directly or indirectly.
+    pub fn reachable_from(&self, function: Word) -> BTreeSet<Word> {
+        let mut reachable = BTreeSet::new();
+        let mut stack = vec![function];
+        while let Some(id) = stack.pop() {
+            if reachable.insert(id) {
+                stack.extend(self.callees(id));
+            }
+        }
+        reachable
+    }
+
+    /// Groups of functions that call each other recursively. A function that
+    /// only calls itself forms a group of its own. Groups and the functions in
+    /// them are in module order.
+    pub fn recursion(&self) -> &[Vec<Word>] {
+        &self.recursion
+    }
+}
+
+/// The strongly connected components of the call graph with more than one
+/// function or a call to itself, found with Kosaraju's algorithm.
+fn recursion(functions: &[Word], callees: &BTreeMap<Word, BTreeSet<Word>>) -> Vec<Vec<Word>> {
+    let mut forward: HashMap<Word, Vec<Word>> = HashMap::new();
+    let mut backward: HashMap<Word, Vec<Word>> = HashMap::new();
+    for (&caller, callees) in callees {
+        forward.insert(caller, callees.iter().cloned().collect());
+        for &callee in callees {
+            backward.entry(callee).or_default().push(caller);
+        }
+    }
+    let mut finished: Vec<Word> = DepthFirst::new(&forward, functions.to_vec())
+        .filter_map(|event| match event {
+            Event::Leave(function) => Some(function),
+            _ => None,
+        })
+        .collect();
+    finished.reverse();
+
+    // Every tree of the search on the reversed graph is a component
+    let mut groups: Vec<Vec<Word>> = Vec::new();
+    let mut depth = 0;
+    for event in DepthFirst::new(&backward, finished) {
+        match event {
+            Event::Enter(function) => {
+                if depth == 0 {
+                    groups.push(Vec::new());
+                }
+                depth += 1;
+                groups.last_mut().expect("group").push(function);
+            }
+            Event::Leave(_) => depth -= 1,
+            Event::BackEdge(..) => {}
+        }
+    }
+
+    let order: HashMap<Word, usize> = functions
+        .iter()
+        .enumerate()
+        .map(|(index, &function)| (function, index))
+        .collect();
+    groups.retain(|group| {
+        group.len() > 1
+            || callees
+                .get(&group[0])
+                .into_iter()
+                .any(|callees| callees.contains(&group[0]))
+    });
+    for group in &mut groups {
+        group.sort_by_key(|function| order.get(function).cloned());
+    }
+    groups.sort_by_key(|group| order.get(&group[0]).cloned());
+    groups
+}
@@ -0,0 +1,421 @@
+//! The `NonSemantic.Shader.DebugInfo.100` extended instruction set.
+//!
+//! All operands of these instructions are ids, integers are passed as ids of
+//! 32 bit `OpConstant`s and strings as ids of `OpString`s.
+use super::{SourceLocation, SpirvModule};
+use rspirv::dr::{Block, Instruction, Module, Operand};
+use spirv::Word;
+use std::collections::{BTreeMap, HashMap};
+
+/// The name of the instruction set in `OpExtInstImport`.
+pub const SHADER_DEBUG_INFO: &str = "NonSemantic.Shader.DebugInfo.100";
+
+const DEBUG_COMPILATION_UNIT: u32 = 2;
+const DEBUG_FUNCTION: u32 = 21;
+const DEBUG_LEXICAL_BLOCK: u32 = 22;
+const DEBUG_SCOPE: u32 = 24;
+const DEBUG_NO_SCOPE: u32 = 25;
+const DEBUG_INLINED_AT: u32 = 26;
+const DEBUG_LOCAL_VARIABLE: u32 = 27;
+const DEBUG_DECLARE: u32 = 29;
+const DEBUG_VALUE: u32 = 30;
+const DEBUG_SOURCE: u32 = 36;
+const DEBUG_FUNCTION_DEFINITION: u32 = 101;
+const DEBUG_SOURCE_CONTINUED: u32 = 102;
+const DEBUG_LINE: u32 = 103;
+const DEBUG_NO_LINE: u32 = 104;
+
+/// The name of every instruction of the set.
+fn instruction_name(number: u32) -> Option<&'static str> {
+    Some(match number {
+        1 => "DebugInfoNone",
+        2 => "DebugCompilationUnit",
+        3 => "DebugTypeBasic",
+        4 => "DebugTypePointer",
+        5 => "DebugTypeQualifier",
+        6 => "DebugTypeArray",
+        7 => "DebugTypeVector",
+        8 => "DebugTypedef",
+        9 => "DebugTypeFunction",
+        10 => "DebugTypeEnum",
+        11 => "DebugTypeComposite",
+        12 => "DebugTypeMember",
+        13 => "DebugTypeInheritance",
+        14 => "DebugTypePtrToMember",
+        15 => "DebugTypeTemplate",
+        16 => "DebugTypeTemplateParameter",
+        17 => "DebugTypeTemplateTemplateParameter",
+        18 => "DebugTypeTemplateParameterPack",
+        19 => "DebugGlobalVariable",
+        20 => "DebugFunctionDeclaration",
+        21 => "DebugFunction",
+        22 => "DebugLexicalBlock",
+        23 => "DebugLexicalBlockDiscriminator",
+        24 => "DebugScope",
+        25 => "DebugNoScope",
+        26 => "DebugInlinedAt",
+        27 => "DebugLocalVariable",
+        28 => "DebugInlinedVariable",
+        29 => "DebugDeclare",
+        30 => "DebugValue",
+        31 => "DebugOperation",
+        32 => "DebugExpression",
+        33 => "DebugMacroDef",
+        34 => "DebugMacroUndef",
+        35 => "DebugImportedEntity",
+        36 => "DebugSource",
+        101 => "DebugFunctionDefinition",
+        102 => "DebugSourceContinued",
+        103 => "DebugLine",
+        104 => "DebugNoLine",
+        105 => "DebugBuildIdentifier",
+        106 => "DebugStoragePath",
+        107 => "DebugEntryPoint",
+        108 => "DebugTypeMatrix",
+        _ => return None,
+    })
+}
+
+/// The debug information of a module, decoded from the
+/// `NonSemantic.Shader.DebugInfo.100` instructions.
+#[derive(Clone, Debug, Default)]
+pub struct DebugInfo {
+    /// The id of the `OpExtInstImport`, `None` if the module doesn't use the set
+    set: Option<Word>,
+    /// The instructions outside of functions by result id
+    globals: HashMap<Word, Instruction>,
+    /// The value of every 32 bit integer constant
+    constants: HashMap<Word, u32>,
+    strings: HashMap<Word, String>,
+    /// The source variable of every `OpVariable` and value named by a
+    /// `DebugDeclare` or `DebugValue`
+    pub local_variables: BTreeMap<Word, String>,
+    /// The name of every function with a `DebugFunctionDefinition`
+    pub function_names: BTreeMap<Word, String>,
+}
+
+impl DebugInfo {
+    pub(crate) fn new(module: &Module) -> Self {
+        let set = module
+            .ext_inst_imports
+            .iter()
+            .find_map(|inst| match inst.operands.as_slice() {
+                [Operand::LiteralString(name)] if name == SHADER_DEBUG_INFO => inst.result_id,
+                _ => None,
+            });
+        let mut info = DebugInfo {
+            set,
+            ..DebugInfo::default()
+        };
+        if set.is_none() {
+            return info;
+        }
+        for inst in &module.debugs {
+            if let (Some(id), [Operand::LiteralString(string)]) =
+                (inst.result_id, inst.operands.as_slice())
+            {
+                info.strings.insert(id, string.clone());
+            }
+        }
+        for inst in &module.types_global_values {
+            match (inst.class.opcode, inst.result_id, inst.operands.as_slice()) {
+                (spirv::Op::Constant, Some(id), [Operand::LiteralInt32(value)]) => {
+                    info.constants.insert(id, *value);
+                }
+                (spirv::Op::ExtInst, Some(id), _) if info.decode(inst).is_some() => {
+                    info.globals.insert(id, inst.clone());
+                }
+                _ => {}
+            }
+        }
+        for inst in module
+            .functions
+            .iter()
+            .flat_map(|f| &f.blocks)
+            .flat_map(|block| &block.instructions)
+        {
+            let (number, operands) = match info.decode(inst) {
+                Some(decoded) => decoded,
+                None => continue,
+            };
+            match (number, operands.as_slice()) {
+                (DEBUG_DECLARE, [variable, target, ..]) | (DEBUG_VALUE, [variable, target, ..]) => {
+                    if let Some(name) = info.variable_name(*variable) {
+                        info.local_variables.insert(*target, name);
+                    }
+                }
+                (DEBUG_FUNCTION_DEFINITION, [function, definition]) => {
+                    let name = info
+                        .operand_string(info.operands(*function, DEBUG_FUNCTION), 0)
+                        .map(String::from);
+                    if let Some(name) = name {
+                        info.function_names.insert(*definition, name);
+                    }
+                }
+                _ => {}
+            }
+        }
+        info
+    }
+
+    /// Whether the module uses the instruction set.
+    pub fn is_empty(&self) -> bool {
+        self.set.is_none()
+    }
+
+    /// The instruction number and id operands of an instruction of the set.
+    fn decode(&self, inst: &Instruction) -> Option<(u32, Vec<Word>)> {
+        if inst.class.opcode != spirv::Op::ExtInst {
+            return None;
+        }
+        match inst.operands.as_slice() {
+            [Operand::IdRef(set), Operand::LiteralExtInstInteger(number), rest @ ..]
+                if Some(*set) == self.set =>
+            {
+                let ids = rest
+                    .iter()
+                    .filter_map(|operand| match operand {
+                        Operand::IdRef(id) => Some(*id),
+                        _ => None,
+                    })
+                    .collect();
+                Some((*number, ids))
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the instruction belongs to the set.
+    pub fn is_debug_instruction(&self, inst: &Instruction) -> bool {
+        self.decode(inst).is_some()
+    }
+
+    /// The operands of the global instruction `id` if it's a `number` instruction.
+    fn operands(&self, id: Word, number: u32) -> Option<Vec<Word>> {
+        match self.decode(self.globals.get(&id)?) {
+            Some((found, operands)) if found == number => Some(operands),
+            _ => None,
+        }
+    }
+
+    fn operand_string(&self, operands: Option<Vec<Word>>, index: usize) -> Option<&str> {
+        self.strings.get(operands?.get(index)?).map(String::as_str)
+    }
+
+    fn operand_constant(&self, operands: &[Word], index: usize) -> Option<u32> {
+        self.constants.get(operands.get(index)?).cloned()
+    }
+
+    /// The name of a `DebugLocalVariable`.
+    fn variable_name(&self, variable: Word) -> Option<String> {
+        self.operand_string(self.operands(variable, DEBUG_LOCAL_VARIABLE), 0)
+            .map(String::from)
+    }
+
+    /// The `OpString` with the file name of a `DebugSource`.
+    fn source_file(&self, source: Word) -> Option<Word> {
+        self.operands(source, DEBUG_SOURCE)?.first().cloned()
+    }
+
+    /// The file and the text of every `DebugSource` with its
+    /// `DebugSourceContinued` instructions, together with the language of the
+    /// compilation unit using it.
+    pub(crate) fn sources(
+        &self,
+        module: &Module,
+    ) -> Vec<(Word, Option<String>, spirv::SourceLanguage)> {
+        let mut languages = HashMap::new();
+        let mut sources: Vec<(Word, Word, Option<String>)> = Vec::new();
+        for inst in &module.types_global_values {
+            match self.decode(inst) {
+                Some((DEBUG_SOURCE, operands)) => {
+                    let text = operands
+                        .get(1)
+                        .and_then(|text| self.strings.get(text))
+                        .cloned();
+                    if let (Some(id), Some(&file)) = (inst.result_id, operands.first()) {
+                        sources.push((id, file, text));
+                    }
+                }
+                Some((DEBUG_SOURCE_CONTINUED, operands)) => {
+                    let continued = operands.first().and_then(|text| self.strings.get(text));
+                    if let (Some((_, _, Some(text))), Some(continued)) =
+                        (sources.last_mut(), continued)
+                    {
+                        text.push_str(continued);
+                    }
+                }
+                Some((DEBUG_COMPILATION_UNIT, operands)) => {
+                    if let (Some(&source), Some(language)) =
+                        (operands.get(2), self.operand_constant(&operands, 3))
+                    {
+                        languages.insert(source, language);
+                    }
+                }
+                _ => {}
+            }
+        }
+        sources
+            .into_iter()
+            .map(|(id, file, text)| {
+                let language = match languages.get(&id) {
+                    Some(1) => spirv::SourceLanguage::ESSL,
+                    Some(2) => spirv::SourceLanguage::GLSL,
+                    Some(3) => spirv::SourceLanguage::OpenCL_C,
+                    Some(4) => spirv::SourceLanguage::OpenCL_CPP,
+                    Some(5) => spirv::SourceLanguage::HLSL,
+                    _ => spirv::SourceLanguage::Unknown,
+                };
+                (file, text, language)
+            })
+            .collect()
+    }
+
+    /// The location set by the `DebugLine` in effect for every instruction of
+    /// the block.
+    pub fn block_locations(&self, block: &Block) -> Vec<Option<SourceLocation>> {
+        let mut line = None;
+        block
+            .instructions
+            .iter()
+            .map(|inst| {
+                match self.decode(inst) {
+                    Some((DEBUG_LINE, operands)) => {
+                        line = operands.first().and_then(|&source| {
+                            Some(SourceLocation {
+                                file: self.source_file(source)?,
+                                line: self.operand_constant(&operands, 1)?,
+                                column: self.operand_constant(&operands, 3).unwrap_or(0),
+                            })
+                        })
+                    }
+                    Some((DEBUG_NO_LINE, _)) => line = None,
+                    _ => {}
+                }
+                line
+            })
+            .collect()
+    }
+
+    /// The scope set by the `DebugScope` in effect for every instruction of
+    /// the block.
+    pub fn block_scopes(&self, block: &Block) -> Vec<Option<Word>> {
+        let mut scope = None;
+        block
+            .instructions
+            .iter()
+            .map(|inst| {
+                match self.decode(inst) {
+                    Some((DEBUG_SCOPE, operands)) => scope = operands.first().cloned(),
+                    Some((DEBUG_NO_SCOPE, _)) => scope = None,
+                    _ => {}
+                }
+                scope
+            })
+            .collect()
+    }
+
+    /// A readable name for a `DebugFunction`, `DebugLexicalBlock` or
+    /// `DebugCompilationUnit` scope, with the scopes containing it, e.g.
+    /// `main > block at 12:5`.
+    pub fn scope_name(&self, scope: Word) -> String {
+        let mut names = Vec::new();
+        let mut next = Some(scope);
+        while let Some(scope) = next {
+            next = None;
+            // Parents are declared before their children, so this only
+            // guards against malformed modules
+            if names.len() >= 64 {
+                break;
+            }
+            if let Some(operands) = self.operands(scope, DEBUG_FUNCTION) {
+                names.push(
+                    self.operand_string(Some(operands), 0)
+                        .unwrap_or("function")
+                        .to_string(),
+                );
+            } else if let Some(operands) = self.operands(scope, DEBUG_LEXICAL_BLOCK) {
+                let name = self
+                    .strings
+                    .get(operands.get(4).unwrap_or(&0))
+                    .filter(|name| !name.is_empty());
+                names.push(match name {
+                    Some(name) => name.clone(),
+                    None => format!(
+                        "block at {}:{}",
+                        self.operand_constant(&operands, 1).unwrap_or(0),
+                        self.operand_constant(&operands, 2).unwrap_or(0)
+                    ),
+                });
+                next = operands.get(3).cloned();
+            } else if self.operands(scope, DEBUG_COMPILATION_UNIT).is_some() {
+                names.push("compilation unit".to_string());
+            } else {
+                names.push(format!("%{}", scope));
+            }
+        }
+        names.reverse();
+        names.join(" > ")
+    }
+
+    /// Disassembles an instruction of the set with its name instead of its
+    /// number, strings and integer constants inlined and scopes and lines
+    /// decoded. `None` for all other instructions.
+    pub(crate) fn disassemble(&self, module: &SpirvModule, inst: &Instruction) -> Option<String> {
+        let (number, operands) = self.decode(inst)?;
+        let name = instruction_name(number)
+            .map(String::from)
+            .unwrap_or_else(|| format!("Debug{}", number));
+        let rid = module
+            .name_or_id(inst.result_id)
+            .map(|name| format!("{} = ", name))
+            .unwrap_or_default();
+        let operand_text = |id: &Word| match (self.strings.get(id), self.constants.get(id)) {
+            (Some(string), _) => format!("{:?}", string),
+            (_, Some(value)) => value.to_string(),
+            _ => module.operand_name(*id),
+        };
+        let decoded = match number {
+            DEBUG_SCOPE => operands.first().map(|&scope| {
+                let inlined = operands.get(1).and_then(|&at| {
+                    let at = self.operands(at, DEBUG_INLINED_AT)?;
+                    Some(format!(
+                        " (inlined at line {} of {})",
+                        self.operand_constant(&at, 0)?,
+                        self.scope_name(*at.get(1)?)
+                    ))
+                });
+                format!("{}{}", self.scope_name(scope), inlined.unwrap_or_default())
+            }),
+            DEBUG_LINE => operands.first().map(|&source| {
+                let file = self
+                    .source_file(source)
+                    .and_then(|file| self.strings.get(&file))
+                    .cloned()
+                    .unwrap_or_else(|| format!("%{}", source));
+                let value = |index| {
+                    self.operand_constant(&operands, index)
+                        .map_or_else(|| "?".to_string(), |value| value.to_string())
+                };
+                format!(
+                    "{}:{}:{}-{}:{}",
+                    file,
+                    value(1),
+                    value(3),
+                    value(2),
+                    value(4)
+                )
+            }),
+            _ => None,
+        };
+        let operands = decoded.unwrap_or_else(|| {
+            operands
+                .iter()
+                .map(operand_text)
+                .collect::<Vec<String>>()
+                .join(" ")
+        });
+        let space = if operands.is_empty() { "" } else { " " };
+        Some(format!("{}{}{}{}", rid, name, space, operands))
+    }
+}
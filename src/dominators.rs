@@ -0,0 +1,309 @@
+//! Dominator and post-dominator trees.
+//!
+//! Both trees are computed with the iterative algorithm from Cooper, Harvey and
+//! Kennedy, "A Simple, Fast Dominance Algorithm". The trees are rooted in a
+//! virtual node: for dominators its only child is the entry block, for
+//! post-dominators its children are all blocks that leave the function. Blocks
+//! the root can't reach (unreachable blocks, or infinite loops for
+//! post-dominators) are not part of the tree.
+//!
+//! The structural dominator tree of SPIR-V 1.6 §2.11 also counts every
+//! `OpSelectionMerge` and `OpLoopMerge` as an edge from the header to its merge
+//! block and continue target.
+use super::traversal::{DepthFirst, Event};
+use super::{PetSpirv, Terminator};
+use spirv::Word;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DominatorTreeKind {
+    Dominator,
+    PostDominator,
+}
+
+#[derive(Clone, Debug)]
+pub struct DominatorTree {
+    kind: DominatorTreeKind,
+    /// Blocks in postorder of the (possibly reversed) CFG. The virtual root is
+    /// not included, its index is `nodes.len()`.
+    nodes: Vec<Word>,
+    index: HashMap<Word, usize>,
+    /// Predecessors in the direction of the analysis, by index
+    preds: Vec<Vec<usize>>,
+    /// Immediate dominator by index, the virtual root is its own idom
+    idom: Vec<usize>,
+    children: Vec<Vec<usize>>,
+    /// Preorder and postorder numbers of each node in the tree, used to answer
+    /// dominance queries in constant time
+    enter: Vec<usize>,
+    exit: Vec<usize>,
+}
+
+impl DominatorTree {
+    pub fn new(pet: &PetSpirv, kind: DominatorTreeKind) -> Self {
+        let mut succs: HashMap<Word, Vec<Word>> = HashMap::new();
+        let mut roots = Vec::new();
+        match kind {
+            DominatorTreeKind::Dominator => {
+                for &id in pet.block_map.keys() {
+                    succs.insert(id, pet.successors(id));
+                }
+                roots.extend(pet.entry_block());
+            }
+            DominatorTreeKind::PostDominator => {
+                for (&id, block) in &pet.block_map {
+                    succs.entry(id).or_default();
+                    for succ in pet.successors(id) {
+                        succs.entry(succ).or_default().push(id);
+                    }
+                    if Terminator::from_basic_block(block).is_exit() {
+                        roots.push(id);
+                    }
+                }
+            }
+        }
+        Self::from_graph(kind, &roots, &succs)
+    }
+
+    /// The structural dominator tree, where merge and continue declarations
+    /// count as edges. A merge block that is only reached through breaks from
+    /// a nested selection is dominated by its header this way, not by the
+    /// nested selection.
+    pub fn structural(pet: &PetSpirv) -> Self {
+        let mut succs: HashMap<Word, Vec<Word>> = HashMap::new();
+        for (&id, block) in &pet.block_map {
+            let mut targets = pet.successors(id);
+            if let Some(merge) = Terminator::from_basic_block(block).merge() {
+                for target in Some(merge.merge_block())
+                    .into_iter()
+                    .chain(merge.continue_target())
+                {
+                    if pet.block_map.contains_key(&target) && !targets.contains(&target) {
+                        targets.push(target);
+                    }
+                }
+            }
+            succs.insert(id, targets);
+        }
+        let roots: Vec<Word> = pet.entry_block().into_iter().collect();
+        Self::from_graph(DominatorTreeKind::Dominator, &roots, &succs)
+    }
+
+    fn from_graph(
+        kind: DominatorTreeKind,
+        roots: &[Word],
+        succs: &HashMap<Word, Vec<Word>>,
+    ) -> Self {
+        let (nodes, preds) = postorder(roots, succs);
+        let root = nodes.len();
+        let idom = compute_idoms(&preds, root);
+        let index = nodes.iter().enumerate().map(|(i, &id)| (id, i)).collect();
+        let mut children = vec![Vec::new(); root + 1];
+        for (node, &parent) in idom.iter().enumerate().take(root) {
+            children[parent].push(node);
+        }
+        // Reverse postorder puts the children in CFG order
+        for children in &mut children {
+            children.reverse();
+        }
+        let (enter, exit) = number_tree(&children, root);
+        DominatorTree {
+            kind,
+            nodes,
+            index,
+            preds,
+            idom,
+            children,
+            enter,
+            exit,
+        }
+    }
+
+    pub fn kind(&self) -> DominatorTreeKind {
+        self.kind
+    }
+
+    fn root(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Whether the block is part of the tree.
+    pub fn contains(&self, block: Word) -> bool {
+        self.index.contains_key(&block)
+    }
+
+    /// The blocks directly below the virtual root: the entry block for a
+    /// dominator tree, the exit blocks for a post-dominator tree.
+    pub fn roots(&self) -> Vec<Word> {
+        self.children_of(self.root())
+    }
+
+    /// The immediate (post-)dominator of `block`, `None` for roots and blocks
+    /// that are not in the tree.
+    pub fn immediate_dominator(&self, block: Word) -> Option<Word> {
+        let idom = self.idom[*self.index.get(&block)?];
+        if idom == self.root() {
+            None
+        } else {
+            Some(self.nodes[idom])
+        }
+    }
+
+    /// Whether `a` (post-)dominates `b`. Every block dominates itself.
+    pub fn dominates(&self, a: Word, b: Word) -> bool {
+        match (self.index.get(&a), self.index.get(&b)) {
+            (Some(&a), Some(&b)) => self.enter[a] <= self.enter[b] && self.exit[b] <= self.exit[a],
+            _ => false,
+        }
+    }
+
+    /// Whether `a` (post-)dominates `b` and they are different blocks.
+    pub fn strictly_dominates(&self, a: Word, b: Word) -> bool {
+        a != b && self.dominates(a, b)
+    }
+
+    /// The blocks immediately (post-)dominated by `block`.
+    pub fn children(&self, block: Word) -> Vec<Word> {
+        self.index
+            .get(&block)
+            .map(|&index| self.children_of(index))
+            .unwrap_or_default()
+    }
+
+    fn children_of(&self, index: usize) -> Vec<Word> {
+        self.children[index]
+            .iter()
+            .map(|&child| self.nodes[child])
+            .collect()
+    }
+
+    /// Every block of the tree paired with its immediate (post-)dominator.
+    pub fn iter(&self) -> impl Iterator<Item = (Word, Option<Word>)> + '_ {
+        self.nodes
+            .iter()
+            .map(move |&id| (id, self.immediate_dominator(id)))
+    }
+
+    /// The (post-)dominance frontier of every block in the tree.
+    pub fn dominance_frontier(&self) -> BTreeMap<Word, BTreeSet<Word>> {
+        let mut frontier: BTreeMap<Word, BTreeSet<Word>> =
+            self.nodes.iter().map(|&id| (id, BTreeSet::new())).collect();
+        for (block, preds) in self.preds.iter().enumerate() {
+            if preds.len() < 2 {
+                continue;
+            }
+            for &pred in preds {
+                let mut runner = pred;
+                while runner != self.idom[block] && runner != self.root() {
+                    frontier
+                        .get_mut(&self.nodes[runner])
+                        .expect("block")
+                        .insert(self.nodes[block]);
+                    runner = self.idom[runner];
+                }
+            }
+        }
+        frontier
+    }
+}
+
+/// Returns the blocks reachable from `roots` in postorder and the predecessors
+/// of each of them by index. The virtual root gets index `nodes.len()`.
+fn postorder(roots: &[Word], succs: &HashMap<Word, Vec<Word>>) -> (Vec<Word>, Vec<Vec<usize>>) {
+    let nodes: Vec<Word> = DepthFirst::new(succs, roots.to_vec())
+        .filter_map(|event| match event {
+            Event::Leave(id) => Some(id),
+            _ => None,
+        })
+        .collect();
+    let index: HashMap<Word, usize> = nodes.iter().enumerate().map(|(i, &id)| (id, i)).collect();
+    let empty = Vec::new();
+    let root = nodes.len();
+    let mut preds = vec![Vec::new(); root + 1];
+    for &r in roots {
+        if let Some(&r) = index.get(&r) {
+            if !preds[r].contains(&root) {
+                preds[r].push(root);
+            }
+        }
+    }
+    for (i, id) in nodes.iter().enumerate() {
+        for target in succs.get(id).unwrap_or(&empty) {
+            if let Some(&t) = index.get(target) {
+                if !preds[t].contains(&i) {
+                    preds[t].push(i);
+                }
+            }
+        }
+    }
+    (nodes, preds)
+}
+
+fn compute_idoms(preds: &[Vec<usize>], root: usize) -> Vec<usize> {
+    const UNDEFINED: usize = usize::MAX;
+    let mut idom = vec![UNDEFINED; root + 1];
+    idom[root] = root;
+    let intersect = |idom: &[usize], mut a: usize, mut b: usize| {
+        while a != b {
+            while a < b {
+                a = idom[a];
+            }
+            while b < a {
+                b = idom[b];
+            }
+        }
+        a
+    };
+    let mut changed = true;
+    while changed {
+        changed = false;
+        // Reverse postorder, skipping the root
+        for block in (0..root).rev() {
+            let mut new_idom = UNDEFINED;
+            for &pred in &preds[block] {
+                if idom[pred] == UNDEFINED {
+                    continue;
+                }
+                new_idom = if new_idom == UNDEFINED {
+                    pred
+                } else {
+                    intersect(&idom, pred, new_idom)
+                };
+            }
+            if new_idom != idom[block] {
+                idom[block] = new_idom;
+                changed = true;
+            }
+        }
+    }
+    idom
+}
+
+/// Numbers the nodes of the tree in pre- and postorder.
+fn number_tree(children: &[Vec<usize>], root: usize) -> (Vec<usize>, Vec<usize>) {
+    let mut enter = vec![0; root + 1];
+    let mut exit = vec![0; root + 1];
+    for (counter, event) in DepthFirst::new(children, vec![root]).enumerate() {
+        match event {
+            Event::Enter(node) => enter[node] = counter,
+            Event::Leave(node) => exit[node] = counter,
+            // A tree has none
+            Event::BackEdge(..) => {}
+        }
+    }
+    (enter, exit)
+}
+
+impl<'spir> PetSpirv<'spir> {
+    pub fn dominator_tree(&self) -> DominatorTree {
+        DominatorTree::new(self, DominatorTreeKind::Dominator)
+    }
+
+    pub fn post_dominator_tree(&self) -> DominatorTree {
+        DominatorTree::new(self, DominatorTreeKind::PostDominator)
+    }
+
+    pub fn structural_dominator_tree(&self) -> DominatorTree {
+        DominatorTree::structural(self)
+    }
+}
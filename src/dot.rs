@@ -0,0 +1,525 @@
+//! Graphviz output.
+use super::{
+    escape_html, BlockOrder, CallGraph, DominatorTree, DominatorTreeKind, Edge, EdgeKind,
+    FunctionFilter, ListingLine, LoopInfo, PetSpirv, SourceView, SpirvModule, Terminator,
+};
+use spirv::Word;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Controls what ends up in the DOT output.
+#[derive(Clone, Debug, Default)]
+pub struct DotOptions {
+    /// Draw the true edge of a conditional branch green and the false edge red.
+    pub color_branches: bool,
+    /// Overlay the dominator tree on the CFG with an edge from every block's
+    /// immediate dominator to the block.
+    pub idom_edges: bool,
+    /// Group the blocks of every natural loop in a cluster.
+    pub loop_clusters: bool,
+    /// Draw blocks and edges that break the structured control flow rules red.
+    pub highlight_violations: bool,
+    /// Leave out blocks that can't be reached from the entry block.
+    pub hide_unreachable: bool,
+    /// The order blocks and their edges are written in.
+    pub block_order: BlockOrder,
+    /// Link blocks containing an `OpFunctionCall` to the cluster of the callee.
+    pub call_edges: bool,
+    /// Show the source lines of blocks with, or instead of, their instructions.
+    pub source_view: SourceView,
+    /// Leave the shader debug info instructions out of the blocks, they still
+    /// provide source lines and scopes.
+    pub hide_debug_info: bool,
+    /// The functions to draw, all of them if the filter is empty.
+    pub functions: FunctionFilter,
+}
+
+/// How a block and its outgoing edges are drawn.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum NodeStyle {
+    Normal,
+    Unreachable,
+    Violation,
+}
+
+/// Writes the control flow graph of every function in the module as DOT to
+/// `test.dot`, panicking if the file can't be written.
+///
+/// Use `export_dot` to choose the options and the path and handle the error.
+pub fn export_spirv_cfg(module: &SpirvModule) {
+    export_dot(module, &DotOptions::default(), "test.dot").expect("test.dot");
+}
+
+/// Writes the control flow graph of every selected function as DOT to `path`.
+pub fn export_dot<P: AsRef<Path>>(
+    module: &SpirvModule,
+    options: &DotOptions,
+    path: P,
+) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    write_spirv_cfg(module, &CallGraph::new(module), options, &mut file)?;
+    file.flush()
+}
+
+/// Writes one `<function name>.dot` file per function into the directory `dir`
+/// and returns the paths of the written files.
+///
+/// Functions without a name are written to `function_<id>.dot`, and the id is
+/// appended to the file name when several functions share the same name, plus
+/// a counter if that name is taken as well.
+pub fn export_spirv_cfg_per_function<P: AsRef<Path>>(
+    module: &SpirvModule,
+    graph: &CallGraph,
+    options: &DotOptions,
+    dir: P,
+) -> io::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    let mut used = HashSet::new();
+    for f in options.functions.select(module, graph) {
+        let s = PetSpirv::new(module, f);
+        let id = s.function_id();
+        let base = module
+            .get_name_fn(f)
+            .map(sanitize_file_name)
+            .unwrap_or_else(|| format!("function_{}", id));
+        // A function can be named like the de-duplicated stem of another one
+        let mut stem = base.clone();
+        let mut attempt = 0;
+        while !used.insert(stem.clone()) {
+            attempt += 1;
+            stem = if attempt == 1 {
+                format!("{}_{}", base, id)
+            } else {
+                format!("{}_{}_{}", base, id, attempt)
+            };
+        }
+        let path = dir.as_ref().join(format!("{}.dot", stem));
+        let mut file = BufWriter::new(File::create(&path)?);
+        s.write_dot(options, &mut file)?;
+        file.flush()?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// Writes the control flow graph of every function in the module as DOT into `write`.
+///
+/// The whole module becomes a single `digraph` with one cluster per function.
+pub fn write_spirv_cfg(
+    module: &SpirvModule,
+    graph: &CallGraph,
+    options: &DotOptions,
+    write: &mut impl Write,
+) -> io::Result<()> {
+    write_dot_header(write)?;
+    for f in options.functions.select(module, graph) {
+        let s = PetSpirv::new(module, f);
+        s.add_fn_to_dot(options, write)?;
+    }
+    if options.call_edges {
+        let selected = options.functions.select_ids(module, graph);
+        // Edges can only end at a cluster border in compound graphs
+        writeln!(write, "compound=true;")?;
+        for call in &graph.calls {
+            if !selected.contains(&call.caller) || !selected.contains(&call.callee) {
+                continue;
+            }
+            writeln!(
+                write,
+                "  {} -> {}[lhead=\"cluster_{}\", style=\"dashed\", color=\"purple\", constraint=false]",
+                call.block, call.callee, call.callee
+            )?;
+        }
+    }
+    writeln!(write, "}}")
+}
+
+/// Writes the call graph of the module as DOT into `write`. Entry points are
+/// labeled with their execution model and calls between recursive functions
+/// are drawn red.
+pub fn write_call_graph(
+    module: &SpirvModule,
+    graph: &CallGraph,
+    options: &DotOptions,
+    write: &mut impl Write,
+) -> io::Result<()> {
+    let selected = options.functions.select_ids(module, graph);
+    write_dot_header(write)?;
+    for &function in graph.functions.iter().filter(|id| selected.contains(id)) {
+        let name = module.name_or_id(Some(function)).expect("name");
+        let entry_points = graph.entry_points_of(function);
+        if entry_points.is_empty() {
+            writeln!(write, "  {} [shape=\"box\", label={:?}];", function, name)?;
+        } else {
+            let models: Vec<String> = entry_points
+                .iter()
+                .map(|entry_point| {
+                    format!("{:?} {:?}", entry_point.execution_model, entry_point.name)
+                })
+                .collect();
+            writeln!(
+                write,
+                "  {} [shape=\"box\", style=\"filled,bold\", fillcolor=\"lightblue\", label={:?}];",
+                function,
+                format!("{}\n{}", name, models.join("\n"))
+            )?;
+        }
+    }
+    let mut counts: Vec<((Word, Word), usize)> = Vec::new();
+    for call in &graph.calls {
+        if !selected.contains(&call.caller) || !selected.contains(&call.callee) {
+            continue;
+        }
+        match counts
+            .iter_mut()
+            .find(|(edge, _)| *edge == (call.caller, call.callee))
+        {
+            Some((_, count)) => *count += 1,
+            None => counts.push(((call.caller, call.callee), 1)),
+        }
+    }
+    for ((caller, callee), count) in counts {
+        let mut attributes = Vec::new();
+        if count > 1 {
+            attributes.push(format!("label=\"{}x\"", count));
+        }
+        let recursive = graph
+            .recursion()
+            .iter()
+            .any(|group| group.contains(&caller) && group.contains(&callee));
+        if recursive {
+            attributes.push("color=\"red\"".to_string());
+            attributes.push("penwidth=2".to_string());
+        }
+        if attributes.is_empty() {
+            writeln!(write, "  {} -> {}", caller, callee)?;
+        } else {
+            writeln!(
+                write,
+                "  {} -> {}[{}]",
+                caller,
+                callee,
+                attributes.join(", ")
+            )?;
+        }
+    }
+    writeln!(write, "}}")
+}
+
+/// Writes the dominator or post-dominator tree of every function as DOT into `write`.
+pub fn write_dominator_trees(
+    module: &SpirvModule,
+    graph: &CallGraph,
+    options: &DotOptions,
+    kind: DominatorTreeKind,
+    write: &mut impl Write,
+) -> io::Result<()> {
+    write_dot_header(write)?;
+    for f in options.functions.select(module, graph) {
+        PetSpirv::new(module, f).add_dominator_tree_to_dot(kind, write)?;
+    }
+    writeln!(write, "}}")
+}
+
+fn write_dot_header(write: &mut impl Write) -> io::Result<()> {
+    writeln!(write, "digraph {{")?;
+    writeln!(write, "graph [fontname=\"monospace\"];")?;
+    writeln!(write, "node [fontname=\"monospace\"];")?;
+    writeln!(write, "edge [fontname=\"monospace\"];")
+}
+
+/// Exit blocks get a header color that tells how they leave the function, so
+/// discard paths stand out from regular returns.
+pub(crate) fn header_color(terminator: &Terminator) -> &'static str {
+    match terminator {
+        Terminator::Return | Terminator::ReturnValue { .. } => "palegreen",
+        Terminator::Kill
+        | Terminator::TerminateInvocation
+        | Terminator::TerminateRay
+        | Terminator::IgnoreIntersection => "salmon",
+        Terminator::Unreachable => "lightgray",
+        Terminator::Missing => "orange",
+        _ => "gray",
+    }
+}
+
+fn sanitize_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+impl<'spir> PetSpirv<'spir> {
+    /// Writes this function as a complete DOT `digraph`.
+    pub fn write_dot(&self, options: &DotOptions, write: &mut impl Write) -> io::Result<()> {
+        write_dot_header(write)?;
+        self.add_fn_to_dot(options, write)?;
+        writeln!(write, "}}")
+    }
+
+    /// Writes this function as a `subgraph cluster_<id>` that can be embedded in a
+    /// larger graph.
+    pub fn add_fn_to_dot(&self, options: &DotOptions, write: &mut impl Write) -> io::Result<()> {
+        let fn_name = self.module.get_name_fn(self.function).unwrap_or("Unknown");
+        let fn_id = self.function_id();
+        writeln!(write, "subgraph cluster_{} {{", fn_id)?;
+        writeln!(write, "label={:?};", fn_name)?;
+        writeln!(
+            write,
+            "{id} [shape=\"box\", label={name:?}];",
+            id = fn_id,
+            name = fn_name
+        )?;
+        writeln!(write, "{{ rank=\"min\"; {}; }}", fn_id)?;
+        if let Some(entry) = self.entry_block() {
+            writeln!(write, "{} -> {}", fn_id, entry)?;
+        }
+
+        let order = self.ordered_blocks(options.block_order);
+        let reachable = self.reachable_blocks();
+        let drawn = |id: &Word| !options.hide_unreachable || reachable.contains(id);
+        let mut red_blocks = HashSet::new();
+        let mut red_edges = HashSet::new();
+        if options.highlight_violations {
+            for violation in self.validate() {
+                red_blocks.extend(violation.blocks());
+                red_edges.extend(violation.edges());
+            }
+        }
+        let node_style = |id: Word| {
+            if red_blocks.contains(&id) {
+                NodeStyle::Violation
+            } else if reachable.contains(&id) {
+                NodeStyle::Normal
+            } else {
+                NodeStyle::Unreachable
+            }
+        };
+
+        if options.loop_clusters {
+            let loops = self.loop_info();
+            for &id in &order {
+                if loops.innermost_loop(id).is_none() && drawn(&id) {
+                    self.write_block_node(id, options, node_style(id), write)?;
+                }
+            }
+            for index in loops.top_level() {
+                self.write_loop_cluster(&loops, index, &order, options, &node_style, write)?;
+            }
+        } else {
+            for &id in &order {
+                if drawn(&id) {
+                    self.write_block_node(id, options, node_style(id), write)?;
+                }
+            }
+        }
+
+        // Back edges don't take part in ranking, so the entry block stays on
+        // top and the ranks only follow the forward flow of the function.
+        let back_edges: HashSet<(Word, Word)> = self.back_edges().into_iter().collect();
+        for &node in order.iter().filter(|id| drawn(id)) {
+            let terminator = Terminator::from_basic_block(self.get_block(node));
+            for edge in terminator.edges() {
+                if !drawn(&edge.target) {
+                    continue;
+                }
+                let style = if !reachable.contains(&node) {
+                    NodeStyle::Unreachable
+                } else if red_edges.contains(&(node, edge.target)) {
+                    NodeStyle::Violation
+                } else {
+                    NodeStyle::Normal
+                };
+                let mut attributes = self.dot_edge_attributes(options, &terminator, &edge, style);
+                if back_edges.contains(&(node, edge.target)) {
+                    attributes.push("constraint=false".to_string());
+                }
+                writeln!(
+                    write,
+                    "  {node} -> {target}{attributes}",
+                    node = node,
+                    target = edge.target,
+                    attributes = if attributes.is_empty() {
+                        String::new()
+                    } else {
+                        format!("[{}]", attributes.join(", "))
+                    }
+                )?;
+            }
+        }
+        if options.idom_edges {
+            for (block, idom) in self.dominator_tree().iter() {
+                if let Some(idom) = idom {
+                    writeln!(
+                        write,
+                        "  {} -> {}[style=\"dotted\", color=\"gray50\", arrowhead=\"empty\", constraint=false]",
+                        idom, block
+                    )?;
+                }
+            }
+        }
+        writeln!(write, "}}")
+    }
+
+    /// Writes the (post-)dominator tree of this function as a cluster. The
+    /// virtual root is drawn as the function itself for dominator trees and as
+    /// an `exit` node for post-dominator trees.
+    pub fn add_dominator_tree_to_dot(
+        &self,
+        kind: DominatorTreeKind,
+        write: &mut impl Write,
+    ) -> io::Result<()> {
+        let fn_name = self.module.get_name_fn(self.function).unwrap_or("Unknown");
+        let fn_id = self.function_id();
+        let tree = DominatorTree::new(self, kind);
+        writeln!(write, "subgraph cluster_{} {{", fn_id)?;
+        writeln!(write, "label={:?};", fn_name)?;
+        let root = match kind {
+            DominatorTreeKind::Dominator => {
+                writeln!(write, "{} [shape=\"box\", label={:?}];", fn_id, fn_name)?;
+                format!("{}", fn_id)
+            }
+            DominatorTreeKind::PostDominator => {
+                writeln!(write, "exit_{} [shape=\"box\", label=\"exit\"];", fn_id)?;
+                format!("exit_{}", fn_id)
+            }
+        };
+        for (block, _) in tree.iter() {
+            let name = self.module.name_or_id(Some(block)).expect("name");
+            writeln!(write, "  {} [shape=\"box\", label={:?}];", block, name)?;
+        }
+        for root_block in tree.roots() {
+            writeln!(write, "  {} -> {}", root, root_block)?;
+        }
+        for (block, idom) in tree.iter() {
+            if let Some(idom) = idom {
+                writeln!(write, "  {} -> {}", idom, block)?;
+            }
+        }
+        writeln!(write, "}}")
+    }
+
+    fn write_block_node(
+        &self,
+        id: Word,
+        options: &DotOptions,
+        style: NodeStyle,
+        write: &mut impl Write,
+    ) -> io::Result<()> {
+        let name = self.module.name_or_id(Some(id)).expect("name");
+        let terminator = Terminator::from_basic_block(self.get_block(id));
+        writeln!(write, "  {id} [shape=none, label=<", id = id,)?;
+        let (table, color) = match style {
+            NodeStyle::Normal => ("<table>", header_color(&terminator)),
+            NodeStyle::Unreachable => ("<table color=\"gray60\" style=\"dashed\">", "gray90"),
+            NodeStyle::Violation => (
+                "<table color=\"red\" border=\"3\">",
+                header_color(&terminator),
+            ),
+        };
+        writeln!(write, "\t{}", table)?;
+        writeln!(
+            write,
+            "\t\t<tr><td align=\"center\" bgcolor=\"{color}\" colspan=\"1\">{name}</td></tr>",
+            color = color,
+            name = name
+        )?;
+        writeln!(write, "\t\t<tr><td align=\"left\" balign=\"left\">")?;
+        for line in self.block_listing(id, options.source_view, options.hide_debug_info) {
+            match line {
+                ListingLine::Instruction(text) => {
+                    writeln!(write, "\t\t\t{}<br/>", escape_html(&text))?
+                }
+                ListingLine::Source(text) => writeln!(
+                    write,
+                    "\t\t\t<font color=\"blue4\"><i>{}</i></font><br/>",
+                    escape_html(&text)
+                )?,
+            }
+        }
+        writeln!(write, "\t</td></tr></table>>];")
+    }
+
+    /// Writes the blocks of a loop as a cluster, nested loops become nested clusters.
+    fn write_loop_cluster(
+        &self,
+        loops: &LoopInfo,
+        index: usize,
+        order: &[Word],
+        options: &DotOptions,
+        node_style: &impl Fn(Word) -> NodeStyle,
+        write: &mut impl Write,
+    ) -> io::Result<()> {
+        let l = &loops.loops[index];
+        let header = self.module.name_or_id(Some(l.header)).expect("name");
+        writeln!(write, "subgraph cluster_loop_{} {{", l.header)?;
+        writeln!(write, "style=\"dashed\";")?;
+        writeln!(write, "color=\"blue\";")?;
+        writeln!(write, "label={:?};", format!("loop {}", header))?;
+        for &id in order {
+            if loops.innermost_loop(id) == Some(index) {
+                self.write_block_node(id, options, node_style(id), write)?;
+            }
+        }
+        for &child in &l.children {
+            self.write_loop_cluster(loops, child, order, options, node_style, write)?;
+        }
+        writeln!(write, "}}")
+    }
+
+    fn dot_edge_attributes(
+        &self,
+        options: &DotOptions,
+        terminator: &Terminator,
+        edge: &Edge,
+        style: NodeStyle,
+    ) -> Vec<String> {
+        let mut attributes = match edge.kind {
+            EdgeKind::Branch | EdgeKind::Case { .. } => vec![],
+            EdgeKind::True { .. } if options.color_branches => {
+                vec!["color=\"darkgreen\"".to_string()]
+            }
+            EdgeKind::False { .. } if options.color_branches => {
+                vec!["color=\"red3\"".to_string()]
+            }
+            EdgeKind::True { .. } | EdgeKind::False { .. } => vec![],
+            EdgeKind::SelectionMerge => vec!["style=\"dashed\"".to_string()],
+            EdgeKind::LoopMerge => {
+                vec!["style=\"dashed\"".to_string(), "color=\"blue\"".to_string()]
+            }
+            EdgeKind::Continue => {
+                vec!["style=\"dotted\"".to_string(), "color=\"blue\"".to_string()]
+            }
+        };
+        if let EdgeKind::Case { default: true, .. } = edge.kind {
+            attributes.push("style=\"bold\"".to_string());
+        }
+        let label = self.edge_label(terminator, edge);
+        if let Some(label) = label {
+            attributes.push(format!("label={:?}", label));
+        }
+        match style {
+            NodeStyle::Normal => {}
+            NodeStyle::Unreachable => {
+                attributes.retain(|attribute| {
+                    !attribute.starts_with("color=") && !attribute.starts_with("style=")
+                });
+                attributes.push("style=\"dashed\"".to_string());
+                attributes.push("color=\"gray60\"".to_string());
+                attributes.push("fontcolor=\"gray60\"".to_string());
+                attributes.push("arrowhead=\"open\"".to_string());
+            }
+            NodeStyle::Violation => {
+                attributes.retain(|attribute| !attribute.starts_with("color="));
+                attributes.push("color=\"red\"".to_string());
+                attributes.push("penwidth=2".to_string());
+            }
+        }
+        attributes
+    }
+}
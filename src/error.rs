@@ -0,0 +1,71 @@
+use rspirv::binary::{Disassemble, ParseState};
+use rspirv::dr::Instruction;
+use std::{error, fmt, io};
+
+/// Everything that can go wrong while turning a SPIR-V binary into a `SpirvModule`.
+#[derive(Debug)]
+pub enum CfgError {
+    /// The input could not be read.
+    Io(io::Error),
+    /// The binary is shorter than the five word module header.
+    HeaderTruncated { len: usize },
+    /// The first word is not the SPIR-V magic number.
+    BadMagic(u32),
+    /// The header declares a SPIR-V version we don't know about.
+    UnsupportedVersion { major: u8, minor: u8 },
+    /// rspirv rejected the module. `word_offset` points at the offending
+    /// instruction when it can be determined.
+    Parse {
+        state: ParseState,
+        word_offset: Option<usize>,
+    },
+    /// A debug instruction such as `OpName` doesn't have the operands the
+    /// spec requires.
+    MalformedDebugInstruction(Instruction),
+}
+
+impl fmt::Display for CfgError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CfgError::Io(err) => write!(f, "i/o error: {}", err),
+            CfgError::HeaderTruncated { len } => write!(
+                f,
+                "truncated module header: expected at least 20 bytes, found {}",
+                len
+            ),
+            CfgError::BadMagic(magic) => write!(
+                f,
+                "bad magic number {:#010x}, expected {:#010x}",
+                magic,
+                spirv::MAGIC_NUMBER
+            ),
+            CfgError::UnsupportedVersion { major, minor } => {
+                write!(f, "unsupported SPIR-V version {}.{}", major, minor)
+            }
+            CfgError::Parse {
+                state,
+                word_offset: Some(offset),
+            } => write!(f, "parse error at word {}: {}", offset, state),
+            CfgError::Parse { state, .. } => write!(f, "parse error: {}", state),
+            CfgError::MalformedDebugInstruction(inst) => {
+                write!(f, "malformed debug instruction: {}", inst.disassemble())
+            }
+        }
+    }
+}
+
+impl error::Error for CfgError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            CfgError::Io(err) => Some(err),
+            CfgError::Parse { state, .. } => Some(state),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CfgError {
+    fn from(err: io::Error) -> Self {
+        CfgError::Io(err)
+    }
+}
@@ -0,0 +1,342 @@
+//! Names and operand roles of the `GLSL.std.450`, `OpenCL.std` and
+//! `NonSemantic.DebugPrintf` extended instruction sets, so `OpExtInst` reads
+//! like a call instead of a set id followed by an instruction number.
+use super::SpirvModule;
+use rspirv::binary::Disassemble;
+use rspirv::dr::{Instruction, Operand};
+use spirv::Word;
+
+/// An instruction number with its name and the roles of its operands.
+type Entry = (u32, &'static str, &'static [&'static str]);
+
+const GLSL_STD_450: &[Entry] = &[
+    (1, "Round", &["x"]),
+    (2, "RoundEven", &["x"]),
+    (3, "Trunc", &["x"]),
+    (4, "FAbs", &["x"]),
+    (5, "SAbs", &["x"]),
+    (6, "FSign", &["x"]),
+    (7, "SSign", &["x"]),
+    (8, "Floor", &["x"]),
+    (9, "Ceil", &["x"]),
+    (10, "Fract", &["x"]),
+    (11, "Radians", &["degrees"]),
+    (12, "Degrees", &["radians"]),
+    (13, "Sin", &["x"]),
+    (14, "Cos", &["x"]),
+    (15, "Tan", &["x"]),
+    (16, "Asin", &["x"]),
+    (17, "Acos", &["x"]),
+    (18, "Atan", &["y_over_x"]),
+    (19, "Sinh", &["x"]),
+    (20, "Cosh", &["x"]),
+    (21, "Tanh", &["x"]),
+    (22, "Asinh", &["x"]),
+    (23, "Acosh", &["x"]),
+    (24, "Atanh", &["x"]),
+    (25, "Atan2", &["y", "x"]),
+    (26, "Pow", &["x", "y"]),
+    (27, "Exp", &["x"]),
+    (28, "Log", &["x"]),
+    (29, "Exp2", &["x"]),
+    (30, "Log2", &["x"]),
+    (31, "Sqrt", &["x"]),
+    (32, "InverseSqrt", &["x"]),
+    (33, "Determinant", &["x"]),
+    (34, "MatrixInverse", &["x"]),
+    (35, "Modf", &["x", "i"]),
+    (36, "ModfStruct", &["x"]),
+    (37, "FMin", &["x", "y"]),
+    (38, "UMin", &["x", "y"]),
+    (39, "SMin", &["x", "y"]),
+    (40, "FMax", &["x", "y"]),
+    (41, "UMax", &["x", "y"]),
+    (42, "SMax", &["x", "y"]),
+    (43, "FClamp", &["x", "minVal", "maxVal"]),
+    (44, "UClamp", &["x", "minVal", "maxVal"]),
+    (45, "SClamp", &["x", "minVal", "maxVal"]),
+    (46, "FMix", &["x", "y", "a"]),
+    (47, "IMix", &["x", "y", "a"]),
+    (48, "Step", &["edge", "x"]),
+    (49, "SmoothStep", &["edge0", "edge1", "x"]),
+    (50, "Fma", &["a", "b", "c"]),
+    (51, "Frexp", &["x", "exp"]),
+    (52, "FrexpStruct", &["x"]),
+    (53, "Ldexp", &["x", "exp"]),
+    (54, "PackSnorm4x8", &["v"]),
+    (55, "PackUnorm4x8", &["v"]),
+    (56, "PackSnorm2x16", &["v"]),
+    (57, "PackUnorm2x16", &["v"]),
+    (58, "PackHalf2x16", &["v"]),
+    (59, "PackDouble2x32", &["v"]),
+    (60, "UnpackSnorm2x16", &["p"]),
+    (61, "UnpackUnorm2x16", &["p"]),
+    (62, "UnpackHalf2x16", &["v"]),
+    (63, "UnpackSnorm4x8", &["p"]),
+    (64, "UnpackUnorm4x8", &["p"]),
+    (65, "UnpackDouble2x32", &["v"]),
+    (66, "Length", &["x"]),
+    (67, "Distance", &["p0", "p1"]),
+    (68, "Cross", &["x", "y"]),
+    (69, "Normalize", &["x"]),
+    (70, "FaceForward", &["N", "I", "Nref"]),
+    (71, "Reflect", &["I", "N"]),
+    (72, "Refract", &["I", "N", "eta"]),
+    (73, "FindILsb", &["value"]),
+    (74, "FindSMsb", &["value"]),
+    (75, "FindUMsb", &["value"]),
+    (76, "InterpolateAtCentroid", &["interpolant"]),
+    (77, "InterpolateAtSample", &["interpolant", "sample"]),
+    (78, "InterpolateAtOffset", &["interpolant", "offset"]),
+    (79, "NMin", &["x", "y"]),
+    (80, "NMax", &["x", "y"]),
+    (81, "NClamp", &["x", "minVal", "maxVal"]),
+];
+
+const OPENCL_STD: &[Entry] = &[
+    // Math
+    (0, "acos", &["x"]),
+    (1, "acosh", &["x"]),
+    (2, "acospi", &["x"]),
+    (3, "asin", &["x"]),
+    (4, "asinh", &["x"]),
+    (5, "asinpi", &["x"]),
+    (6, "atan", &["y_over_x"]),
+    (7, "atan2", &["y", "x"]),
+    (8, "atanh", &["x"]),
+    (9, "atanpi", &["x"]),
+    (10, "atan2pi", &["y", "x"]),
+    (11, "cbrt", &["x"]),
+    (12, "ceil", &["x"]),
+    (13, "copysign", &["x", "y"]),
+    (14, "cos", &["x"]),
+    (15, "cosh", &["x"]),
+    (16, "cospi", &["x"]),
+    (17, "erfc", &["x"]),
+    (18, "erf", &["x"]),
+    (19, "exp", &["x"]),
+    (20, "exp2", &["x"]),
+    (21, "exp10", &["x"]),
+    (22, "expm1", &["x"]),
+    (23, "fabs", &["x"]),
+    (24, "fdim", &["x", "y"]),
+    (25, "floor", &["x"]),
+    (26, "fma", &["a", "b", "c"]),
+    (27, "fmax", &["x", "y"]),
+    (28, "fmin", &["x", "y"]),
+    (29, "fmod", &["x", "y"]),
+    (30, "fract", &["x", "ptr"]),
+    (31, "frexp", &["x", "exp"]),
+    (32, "hypot", &["x", "y"]),
+    (33, "ilogb", &["x"]),
+    (34, "ldexp", &["x", "k"]),
+    (35, "lgamma", &["x"]),
+    (36, "lgamma_r", &["x", "signp"]),
+    (37, "log", &["x"]),
+    (38, "log2", &["x"]),
+    (39, "log10", &["x"]),
+    (40, "log1p", &["x"]),
+    (41, "logb", &["x"]),
+    (42, "mad", &["a", "b", "c"]),
+    (43, "maxmag", &["x", "y"]),
+    (44, "minmag", &["x", "y"]),
+    (45, "modf", &["x", "iptr"]),
+    (46, "nan", &["nancode"]),
+    (47, "nextafter", &["x", "y"]),
+    (48, "pow", &["x", "y"]),
+    (49, "pown", &["x", "y"]),
+    (50, "powr", &["x", "y"]),
+    (51, "remainder", &["x", "y"]),
+    (52, "remquo", &["x", "y", "quo"]),
+    (53, "rint", &["x"]),
+    (54, "rootn", &["x", "y"]),
+    (55, "round", &["x"]),
+    (56, "rsqrt", &["x"]),
+    (57, "sin", &["x"]),
+    (58, "sincos", &["x", "cosval"]),
+    (59, "sinh", &["x"]),
+    (60, "sinpi", &["x"]),
+    (61, "sqrt", &["x"]),
+    (62, "tan", &["x"]),
+    (63, "tanh", &["x"]),
+    (64, "tanpi", &["x"]),
+    (65, "tgamma", &["x"]),
+    (66, "trunc", &["x"]),
+    (67, "half_cos", &["x"]),
+    (68, "half_divide", &["x", "y"]),
+    (69, "half_exp", &["x"]),
+    (70, "half_exp2", &["x"]),
+    (71, "half_exp10", &["x"]),
+    (72, "half_log", &["x"]),
+    (73, "half_log2", &["x"]),
+    (74, "half_log10", &["x"]),
+    (75, "half_powr", &["x", "y"]),
+    (76, "half_recip", &["x"]),
+    (77, "half_rsqrt", &["x"]),
+    (78, "half_sin", &["x"]),
+    (79, "half_sqrt", &["x"]),
+    (80, "half_tan", &["x"]),
+    (81, "native_cos", &["x"]),
+    (82, "native_divide", &["x", "y"]),
+    (83, "native_exp", &["x"]),
+    (84, "native_exp2", &["x"]),
+    (85, "native_exp10", &["x"]),
+    (86, "native_log", &["x"]),
+    (87, "native_log2", &["x"]),
+    (88, "native_log10", &["x"]),
+    (89, "native_powr", &["x", "y"]),
+    (90, "native_recip", &["x"]),
+    (91, "native_rsqrt", &["x"]),
+    (92, "native_sin", &["x"]),
+    (93, "native_sqrt", &["x"]),
+    (94, "native_tan", &["x"]),
+    // Common
+    (95, "fclamp", &["x", "minval", "maxval"]),
+    (96, "degrees", &["radians"]),
+    (97, "fmax_common", &["x", "y"]),
+    (98, "fmin_common", &["x", "y"]),
+    (99, "mix", &["x", "y", "a"]),
+    (100, "radians", &["degrees"]),
+    (101, "step", &["edge", "x"]),
+    (102, "smoothstep", &["edge0", "edge1", "x"]),
+    (103, "sign", &["x"]),
+    // Geometric
+    (104, "cross", &["p0", "p1"]),
+    (105, "distance", &["p0", "p1"]),
+    (106, "length", &["p"]),
+    (107, "normalize", &["p"]),
+    (108, "fast_distance", &["p0", "p1"]),
+    (109, "fast_length", &["p"]),
+    (110, "fast_normalize", &["p"]),
+    // Integer
+    (141, "s_abs", &["x"]),
+    (142, "s_abs_diff", &["x", "y"]),
+    (143, "s_add_sat", &["x", "y"]),
+    (144, "u_add_sat", &["x", "y"]),
+    (145, "s_hadd", &["x", "y"]),
+    (146, "u_hadd", &["x", "y"]),
+    (147, "s_rhadd", &["x", "y"]),
+    (148, "u_rhadd", &["x", "y"]),
+    (149, "s_clamp", &["x", "minval", "maxval"]),
+    (150, "u_clamp", &["x", "minval", "maxval"]),
+    (151, "clz", &["x"]),
+    (152, "ctz", &["x"]),
+    (153, "s_mad_hi", &["a", "b", "c"]),
+    (154, "u_mad_sat", &["x", "y", "z"]),
+    (155, "s_mad_sat", &["x", "y", "z"]),
+    (156, "s_max", &["x", "y"]),
+    (157, "u_max", &["x", "y"]),
+    (158, "s_min", &["x", "y"]),
+    (159, "u_min", &["x", "y"]),
+    (160, "s_mul_hi", &["x", "y"]),
+    (161, "rotate", &["v", "i"]),
+    (162, "s_sub_sat", &["x", "y"]),
+    (163, "u_sub_sat", &["x", "y"]),
+    (164, "u_upsample", &["hi", "lo"]),
+    (165, "s_upsample", &["hi", "lo"]),
+    (166, "popcount", &["x"]),
+    (167, "s_mad24", &["x", "y", "z"]),
+    (168, "u_mad24", &["x", "y", "z"]),
+    (169, "s_mul24", &["x", "y"]),
+    (170, "u_mul24", &["x", "y"]),
+    (201, "u_abs", &["x"]),
+    (202, "u_abs_diff", &["x", "y"]),
+    (203, "u_mul_hi", &["x", "y"]),
+    (204, "u_mad_hi", &["a", "b", "c"]),
+    // Vector loads and stores
+    (171, "vloadn", &["offset", "p", "n"]),
+    (172, "vstoren", &["data", "offset", "p"]),
+    (173, "vload_half", &["offset", "p"]),
+    (174, "vload_halfn", &["offset", "p", "n"]),
+    (175, "vstore_half", &["data", "offset", "p"]),
+    (176, "vstore_half_r", &["data", "offset", "p", "mode"]),
+    (177, "vstore_halfn", &["data", "offset", "p"]),
+    (178, "vstore_halfn_r", &["data", "offset", "p", "mode"]),
+    (179, "vloada_halfn", &["offset", "p", "n"]),
+    (180, "vstorea_halfn", &["data", "offset", "p"]),
+    (181, "vstorea_halfn_r", &["data", "offset", "p", "mode"]),
+    // Miscellaneous vector
+    (182, "shuffle", &["x", "shuffle_mask"]),
+    (183, "shuffle2", &["x", "y", "shuffle_mask"]),
+    // Misc
+    (184, "printf", &["format"]),
+    (185, "prefetch", &["ptr", "num_elements"]),
+    // Relational
+    (186, "bitselect", &["a", "b", "c"]),
+    (187, "select", &["a", "b", "c"]),
+];
+
+const DEBUG_PRINTF: &[Entry] = &[(1, "DebugPrintf", &["format"])];
+
+/// The instructions of the known set imported as `name`.
+fn instruction_set(name: &str) -> Option<&'static [Entry]> {
+    match name {
+        "GLSL.std.450" => Some(GLSL_STD_450),
+        "OpenCL.std" => Some(OPENCL_STD),
+        "NonSemantic.DebugPrintf" => Some(DEBUG_PRINTF),
+        _ => None,
+    }
+}
+
+/// The name and the instructions of the set imported as `set`, if it's one of
+/// the known sets.
+fn imported_set(module: &SpirvModule, set: Word) -> Option<(&str, &'static [Entry])> {
+    module
+        .module
+        .ext_inst_imports
+        .iter()
+        .find(|inst| inst.result_id == Some(set))
+        .and_then(|inst| match inst.operands.as_slice() {
+            [Operand::LiteralString(name)] => Some((name.as_str(), instruction_set(name)?)),
+            _ => None,
+        })
+}
+
+/// Disassembles an `OpExtInst` of a known set as a call, e.g.
+/// `%9 = OpExtInst %float GLSL.std.450 FMix(x: %6, y: %7, a: %8)`. Operands
+/// beyond the known roles, like the values of a printf, are listed without a
+/// role and strings are inlined. `None` for all other instructions.
+pub(crate) fn disassemble(module: &SpirvModule, inst: &Instruction) -> Option<String> {
+    if inst.class.opcode != spirv::Op::ExtInst {
+        return None;
+    }
+    let (set, number, operands) = match inst.operands.as_slice() {
+        [Operand::IdRef(set), Operand::LiteralExtInstInteger(number), rest @ ..] => {
+            (*set, *number, rest)
+        }
+        _ => return None,
+    };
+    let (set_name, entries) = imported_set(module, set)?;
+    let &(_, name, roles) = entries.iter().find(|entry| entry.0 == number)?;
+    let arguments: Vec<String> = operands
+        .iter()
+        .enumerate()
+        .map(|(index, operand)| {
+            let value = match *operand {
+                Operand::IdRef(id) => match module.source_map.strings.get(&id) {
+                    Some(string) => format!("{:?}", string),
+                    None => module.operand_name(id),
+                },
+                _ => operand.disassemble(),
+            };
+            match roles.get(index) {
+                Some(role) => format!("{}: {}", role, value),
+                None => value,
+            }
+        })
+        .collect();
+    Some(format!(
+        "{rid}OpExtInst{rtype} {set} {name}({arguments})",
+        rid = module
+            .name_or_id(inst.result_id)
+            .map(|name| format!("{} = ", name))
+            .unwrap_or_default(),
+        rtype = inst
+            .result_type
+            .map(|id| format!(" {}", module.operand_name(id)))
+            .unwrap_or_default(),
+        set = set_name,
+        name = name,
+        arguments = arguments.join(", ")
+    ))
+}
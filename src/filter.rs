@@ -0,0 +1,102 @@
+//! Selecting the functions of a module that get rendered.
+use super::{CallGraph, SpirvModule};
+use regex::Regex;
+use rspirv::dr::Function;
+use spirv::Word;
+use std::collections::BTreeSet;
+
+/// Selects functions by name, result id or entry point. A function is
+/// selected if any of the criteria matches it, an empty filter selects every
+/// function.
+#[derive(Clone, Debug, Default)]
+pub struct FunctionFilter {
+    /// Glob patterns matched against the whole `OpName` of a function, `*`
+    /// matches any number of characters and `?` a single one.
+    pub names: Vec<String>,
+    /// Regular expressions that match anywhere in the `OpName` of a function
+    /// unless they are anchored.
+    pub name_regexes: Vec<Regex>,
+    /// Result ids of functions.
+    pub ids: Vec<Word>,
+    /// Names of entry points. The function of the entry point is selected
+    /// together with every function it calls directly or indirectly.
+    pub entry_points: Vec<String>,
+}
+
+impl FunctionFilter {
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+            && self.name_regexes.is_empty()
+            && self.ids.is_empty()
+            && self.entry_points.is_empty()
+    }
+
+    /// The result ids of the selected functions, `graph` is the call graph of
+    /// `module`.
+    pub fn select_ids(&self, module: &SpirvModule, graph: &CallGraph) -> BTreeSet<Word> {
+        let mut selected = BTreeSet::new();
+        // The call graph lists the functions in module order
+        for (f, &id) in module.module.functions.iter().zip(&graph.functions) {
+            let name_matches = module.get_name_fn(f).is_some_and(|name| {
+                self.names.iter().any(|pattern| glob_match(pattern, name))
+                    || self.name_regexes.iter().any(|regex| regex.is_match(name))
+            });
+            if self.is_empty() || name_matches || self.ids.contains(&id) {
+                selected.insert(id);
+            }
+        }
+        for entry_point in &graph.entry_points {
+            if self.entry_points.contains(&entry_point.name) {
+                selected.extend(graph.reachable_from(entry_point.function));
+            }
+        }
+        selected
+    }
+
+    /// The selected functions in module order.
+    pub fn select<'module>(
+        &self,
+        module: &'module SpirvModule,
+        graph: &CallGraph,
+    ) -> Vec<&'module Function> {
+        let selected = self.select_ids(module, graph);
+        module
+            .module
+            .functions
+            .iter()
+            .zip(&graph.functions)
+            .filter(|(_, id)| selected.contains(id))
+            .map(|(f, _)| f)
+            .collect()
+    }
+}
+
+/// Matches `name` against a glob `pattern` with `*` and `?` wildcards.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // The position of the last `*` and the part of the name it swallowed
+    let mut star = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    star = Some((star_p, star_n + 1));
+                    p = star_p + 1;
+                    n = star_n + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
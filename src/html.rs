@@ -0,0 +1,76 @@
+//! Self-contained HTML viewer.
+//!
+//! The page embeds the JSON export of the module together with the viewer's
+//! script and style sheet, so it works offline and without any fetches. The
+//! layout is computed by the viewer when a function is picked.
+use super::{write_json, CallGraph, FunctionFilter, SpirvModule};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+const VIEWER_JS: &str = include_str!("viewer.js");
+const VIEWER_CSS: &str = include_str!("viewer.css");
+
+/// Writes an interactive viewer for the selected functions to `path`.
+pub fn export_html<P: AsRef<Path>>(
+    module: &SpirvModule,
+    functions: &FunctionFilter,
+    title: &str,
+    path: P,
+) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    write_html(module, &CallGraph::new(module), functions, title, &mut file)?;
+    file.flush()
+}
+
+/// Writes an interactive viewer for the selected functions into `write`.
+pub fn write_html(
+    module: &SpirvModule,
+    graph: &CallGraph,
+    functions: &FunctionFilter,
+    title: &str,
+    write: &mut impl Write,
+) -> io::Result<()> {
+    let mut json = Vec::new();
+    write_json(module, graph, functions, &mut json)?;
+    let json = String::from_utf8(json).expect("utf-8");
+    writeln!(write, "<!DOCTYPE html>")?;
+    writeln!(write, "<html>")?;
+    writeln!(write, "<head>")?;
+    writeln!(write, "<meta charset=\"utf-8\">")?;
+    writeln!(write, "<title>{}</title>", super::escape_html(title))?;
+    writeln!(write, "<style>\n{}</style>", VIEWER_CSS)?;
+    writeln!(write, "</head>")?;
+    writeln!(write, "<body>")?;
+    writeln!(write, "<header>")?;
+    writeln!(write, "<select id=\"function\"></select>")?;
+    writeln!(write, "<button id=\"fit\">Fit</button>")?;
+    writeln!(write, "<button id=\"expand\">Expand all</button>")?;
+    writeln!(write, "<button id=\"collapse\">Collapse all</button>")?;
+    writeln!(
+        write,
+        "<span class=\"hint\">drag to pan, scroll to zoom, click a block to show its instructions</span>"
+    )?;
+    writeln!(write, "</header>")?;
+    writeln!(write, "<div id=\"viewport\">")?;
+    writeln!(
+        write,
+        "<svg id=\"graph\" xmlns=\"http://www.w3.org/2000/svg\">"
+    )?;
+    writeln!(
+        write,
+        "<defs><marker id=\"arrow\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" markerWidth=\"7\" markerHeight=\"7\" orient=\"auto-start-reverse\"><path d=\"M0,0 L10,5 L0,10 z\" fill=\"context-stroke\"/></marker></defs>"
+    )?;
+    writeln!(write, "<g id=\"scene\"></g>")?;
+    writeln!(write, "</svg>")?;
+    writeln!(write, "</div>")?;
+    // `</` can't appear in a script element, `<\/` means the same in JSON
+    writeln!(
+        write,
+        "<script id=\"cfg-data\" type=\"application/json\">\n{}</script>",
+        json.replace("</", "<\\/")
+    )?;
+    writeln!(write, "<script>\n{}</script>", VIEWER_JS)?;
+    writeln!(write, "</body>")?;
+    writeln!(write, "</html>")
+}
@@ -0,0 +1,271 @@
+//! Types and constants spelled out in place of their ids, so
+//! `OpFAdd %float %34 %35` reads `OpFAdd f32 %34 1.0f`.
+use super::SpirvModule;
+use rspirv::dr::{Instruction, Operand};
+use spirv::Word;
+use std::collections::HashMap;
+
+/// Types and constants spelled out longer than this keep their id, a large
+/// struct or array would otherwise take over the whole block.
+const MAX_INLINE_LENGTH: usize = 48;
+
+/// Nested types deeper than this keep their id, pointers can form cycles
+/// through `OpTypeForwardPointer`.
+const MAX_TYPE_DEPTH: usize = 8;
+
+/// The type declarations of a module by result id.
+struct Types<'m> {
+    module: &'m SpirvModule,
+    types: HashMap<Word, &'m Instruction>,
+    /// The 32 bit integer constants, for the length of arrays
+    lengths: HashMap<Word, u32>,
+}
+
+impl<'m> Types<'m> {
+    fn new(module: &'m SpirvModule) -> Self {
+        let types = module
+            .module
+            .types_global_values
+            .iter()
+            .filter(|inst| is_type(inst.class.opcode))
+            .filter_map(|inst| Some((inst.result_id?, inst)))
+            .collect();
+        let lengths = module
+            .module
+            .types_global_values
+            .iter()
+            .filter(|inst| inst.class.opcode == spirv::Op::Constant)
+            .filter_map(|inst| match inst.operands.as_slice() {
+                [Operand::LiteralInt32(length)] => Some((inst.result_id?, *length)),
+                _ => None,
+            })
+            .collect();
+        Types {
+            module,
+            types,
+            lengths,
+        }
+    }
+
+    /// The operand `index` of type `id` if it's an id.
+    fn id_operand(&self, id: Word, index: usize) -> Option<Word> {
+        match self.types.get(&id)?.operands.get(index)? {
+            Operand::IdRef(id) => Some(*id),
+            _ => None,
+        }
+    }
+
+    /// The literal operand `index` of type `id`.
+    fn literal_operand(&self, id: Word, index: usize) -> Option<u32> {
+        match self.types.get(&id)?.operands.get(index)? {
+            Operand::LiteralInt32(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    fn opcode(&self, id: Word) -> Option<spirv::Op> {
+        self.types.get(&id).map(|inst| inst.class.opcode)
+    }
+
+    /// Whether `id` is a signed integer type.
+    fn is_signed(&self, id: Word) -> bool {
+        self.opcode(id) == Some(spirv::Op::TypeInt) && self.literal_operand(id, 1) == Some(1)
+    }
+
+    /// A type written like `vec4<f32>` or `ptr<Function, struct Light>`,
+    /// `None` for ids that aren't types.
+    fn name(&self, id: Word) -> Option<String> {
+        self.name_nested(id, 0)
+    }
+
+    fn name_nested(&self, id: Word, depth: usize) -> Option<String> {
+        let inst = self.types.get(&id)?;
+        let nested = |index: usize| match inst.operands.get(index) {
+            Some(Operand::IdRef(id)) if depth < MAX_TYPE_DEPTH => self
+                .name_nested(*id, depth + 1)
+                .or_else(|| self.module.name_or_id(Some(*id))),
+            Some(Operand::IdRef(id)) => self.module.name_or_id(Some(*id)),
+            _ => None,
+        };
+        let literal = |index| self.literal_operand(id, index);
+        Some(match inst.class.opcode {
+            spirv::Op::TypeVoid => "void".to_string(),
+            spirv::Op::TypeBool => "bool".to_string(),
+            spirv::Op::TypeInt if literal(1)? == 1 => format!("i{}", literal(0)?),
+            spirv::Op::TypeInt => format!("u{}", literal(0)?),
+            spirv::Op::TypeFloat => format!("f{}", literal(0)?),
+            spirv::Op::TypeVector => format!("vec{}<{}>", literal(1)?, nested(0)?),
+            spirv::Op::TypeMatrix => {
+                let column = self.id_operand(id, 0)?;
+                format!(
+                    "mat{}x{}<{}>",
+                    literal(1)?,
+                    self.literal_operand(column, 1)?,
+                    self.name_nested(self.id_operand(column, 0)?, depth + 1)?
+                )
+            }
+            spirv::Op::TypeArray => {
+                let length = self.id_operand(id, 1)?;
+                let length = match self.lengths.get(&length) {
+                    Some(length) => length.to_string(),
+                    None => self.module.name_or_id(Some(length))?,
+                };
+                format!("array<{}, {}>", nested(0)?, length)
+            }
+            spirv::Op::TypeRuntimeArray => format!("array<{}>", nested(0)?),
+            spirv::Op::TypeStruct => match self.module.names.get(&id) {
+                Some(name) => format!("struct {}", name),
+                None => format!(
+                    "struct {{{}}}",
+                    (0..inst.operands.len())
+                        .map(nested)
+                        .collect::<Option<Vec<String>>>()?
+                        .join(", ")
+                ),
+            },
+            spirv::Op::TypePointer => match inst.operands.first()? {
+                Operand::StorageClass(class) => format!("ptr<{:?}, {}>", class, nested(1)?),
+                _ => return None,
+            },
+            spirv::Op::TypeFunction => format!(
+                "fn({}) -> {}",
+                (1..inst.operands.len())
+                    .map(nested)
+                    .collect::<Option<Vec<String>>>()?
+                    .join(", "),
+                nested(0)?
+            ),
+            spirv::Op::TypeImage => match inst.operands.get(1)? {
+                Operand::Dim(dim) => format!("image<{}, {:?}>", nested(0)?, dim),
+                _ => return None,
+            },
+            spirv::Op::TypeSampledImage => format!("sampled_image<{}>", nested(0)?),
+            spirv::Op::TypeSampler => "sampler".to_string(),
+            spirv::Op::TypeAccelerationStructureKHR => "acceleration_structure".to_string(),
+            _ => return None,
+        })
+    }
+
+    /// The name of the constructor of a composite constant of type `id`,
+    /// `vec3` rather than `vec3<f32>` as the constituents show their type.
+    fn constructor(&self, id: Word) -> Option<String> {
+        let literal = |index| self.literal_operand(id, index);
+        Some(match self.opcode(id)? {
+            spirv::Op::TypeVector => format!("vec{}", literal(1)?),
+            spirv::Op::TypeMatrix => {
+                let column = self.id_operand(id, 0)?;
+                format!("mat{}x{}", literal(1)?, self.literal_operand(column, 1)?)
+            }
+            spirv::Op::TypeArray | spirv::Op::TypeRuntimeArray => "array".to_string(),
+            spirv::Op::TypeStruct => self
+                .module
+                .names
+                .get(&id)
+                .cloned()
+                .unwrap_or_else(|| "struct".to_string()),
+            _ => self.name(id)?,
+        })
+    }
+}
+
+fn is_type(opcode: spirv::Op) -> bool {
+    matches!(
+        opcode,
+        spirv::Op::TypeVoid
+            | spirv::Op::TypeBool
+            | spirv::Op::TypeInt
+            | spirv::Op::TypeFloat
+            | spirv::Op::TypeVector
+            | spirv::Op::TypeMatrix
+            | spirv::Op::TypeImage
+            | spirv::Op::TypeSampler
+            | spirv::Op::TypeSampledImage
+            | spirv::Op::TypeArray
+            | spirv::Op::TypeRuntimeArray
+            | spirv::Op::TypeStruct
+            | spirv::Op::TypeOpaque
+            | spirv::Op::TypePointer
+            | spirv::Op::TypeFunction
+            | spirv::Op::TypeEvent
+            | spirv::Op::TypeDeviceEvent
+            | spirv::Op::TypeReserveId
+            | spirv::Op::TypeQueue
+            | spirv::Op::TypePipe
+            | spirv::Op::TypeAccelerationStructureKHR
+    )
+}
+
+impl SpirvModule {
+    /// Shows types structurally in the disassembly, e.g. `vec4<f32>` or
+    /// `ptr<Function, struct Light>` instead of `%12`.
+    pub fn inline_types(&mut self) {
+        let types = Types::new(self);
+        let names: Vec<(Word, String)> = types
+            .types
+            .keys()
+            .filter_map(|&id| Some((id, types.name(id)?)))
+            .filter(|(_, name)| name.chars().count() <= MAX_INLINE_LENGTH)
+            .collect();
+        self.inlined.extend(names);
+    }
+
+    /// Shows the values of scalar and composite constants in the disassembly,
+    /// e.g. `1.0f` or `vec3(0.0f, 1.0f, 0.0f)` instead of `%12`. Specialization
+    /// constants keep their id since their value can be overridden.
+    pub fn inline_constants(&mut self) {
+        let types = Types::new(self);
+        let mut values: HashMap<Word, String> = HashMap::new();
+        for inst in &self.module.types_global_values {
+            let (id, ty) = match (inst.result_id, inst.result_type) {
+                (Some(id), Some(ty)) => (id, ty),
+                _ => continue,
+            };
+            let value = match (inst.class.opcode, inst.operands.as_slice()) {
+                (spirv::Op::ConstantTrue, _) => Some("true".to_string()),
+                (spirv::Op::ConstantFalse, _) => Some("false".to_string()),
+                (spirv::Op::ConstantNull, _) => Some("null".to_string()),
+                (spirv::Op::Constant, [Operand::LiteralInt32(value)]) if types.is_signed(ty) => {
+                    Some((*value as i32).to_string())
+                }
+                (spirv::Op::Constant, [Operand::LiteralInt32(value)]) => {
+                    Some(format!("{}u", value))
+                }
+                (spirv::Op::Constant, [Operand::LiteralInt64(value)]) if types.is_signed(ty) => {
+                    Some(format!("{}l", *value as i64))
+                }
+                (spirv::Op::Constant, [Operand::LiteralInt64(value)]) => {
+                    Some(format!("{}ul", value))
+                }
+                (spirv::Op::Constant, [Operand::LiteralFloat32(value)]) => {
+                    Some(format!("{:?}f", value))
+                }
+                (spirv::Op::Constant, [Operand::LiteralFloat64(value)]) => {
+                    Some(format!("{:?}lf", value))
+                }
+                (spirv::Op::ConstantComposite, constituents) => constituents
+                    .iter()
+                    .map(|operand| match operand {
+                        Operand::IdRef(id) => values.get(id).cloned(),
+                        _ => None,
+                    })
+                    .collect::<Option<Vec<String>>>()
+                    .and_then(|constituents| {
+                        Some(format!(
+                            "{}({})",
+                            types.constructor(ty)?,
+                            constituents.join(", ")
+                        ))
+                    }),
+                _ => None,
+            };
+            if let Some(value) = value {
+                values.insert(id, value);
+            }
+        }
+        self.inlined.extend(
+            values
+                .into_iter()
+                .filter(|(_, value)| value.chars().count() <= MAX_INLINE_LENGTH),
+        );
+    }
+}
@@ -0,0 +1,286 @@
+//! JSON output.
+//!
+//! The document has the following shape, version [`JSON_SCHEMA_VERSION`]:
+//!
+//! ```text
+//! {
+//!   "schema_version": 2,
+//!   "names": { "4": "main", "5": "entry" },   // every OpName by id
+//!   "entry_points": [{ "function": 4, "name": "main", "execution_model": "Fragment" }],
+//!   "functions": [{
+//!     "id": 4,
+//!     "name": "main",              // null without OpName
+//!     "entry_block": 5,            // null for declarations
+//!     "blocks": [{
+//!       "id": 5,
+//!       "name": "entry",           // null without OpName
+//!       "reachable": true,
+//!       "color": "palegreen",      // header color of the DOT output
+//!       "instructions": [{
+//!         "opcode": "Load",
+//!         "result_type": 7,        // null if the instruction has none
+//!         "result_id": 8,          // null if the instruction has none
+//!         "operands": [{ "kind": "IdRef", "value": 9 }],
+//!         "text": "%8 = OpLoad %7 x(%9)"  // disassembly with names
+//!       }]
+//!     }],
+//!     "edges": [{ "source": 5, "target": 6, "kind": "branch", "label": null }]
+//!   }]
+//! }
+//! ```
+//!
+//! Operand kinds are the names of the `rspirv::dr::Operand` variants. Ids and
+//! integer literals have numbers as values, float literals have numbers or
+//! strings for infinities and NaN, everything else has a string.
+//!
+//! Edge kinds are `branch`, `true` and `false` (with a `weight` that is null
+//! without branch weights), `case` (with the case `values` and whether it's
+//! the `default`), `selection_merge`, `loop_merge` and `continue`. Case values
+//! are decimal strings, as 64-bit literals don't fit a JavaScript number. The
+//! `label` is the one the DOT output draws, null for unlabeled edges.
+//!
+//! Fields are only ever added within a schema version, removing or changing
+//! one bumps the version.
+use super::dot::header_color;
+use super::{
+    disassemble_text, operands, opname, CallGraph, EdgeKind, FunctionFilter, PetSpirv, SpirvModule,
+    Terminator,
+};
+use rspirv::dr::{Instruction, Operand};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// The version of the JSON document written by [`write_json`].
+pub const JSON_SCHEMA_VERSION: u32 = 2;
+
+/// Writes the selected functions of the module as JSON to `path`.
+pub fn export_json<P: AsRef<Path>>(
+    module: &SpirvModule,
+    functions: &FunctionFilter,
+    path: P,
+) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    write_json(module, &CallGraph::new(module), functions, &mut file)?;
+    file.flush()
+}
+
+/// Writes the selected functions of the module as JSON into `write`.
+pub fn write_json(
+    module: &SpirvModule,
+    graph: &CallGraph,
+    functions: &FunctionFilter,
+    write: &mut impl Write,
+) -> io::Result<()> {
+    writeln!(write, "{{")?;
+    writeln!(write, "  \"schema_version\": {},", JSON_SCHEMA_VERSION)?;
+    let names: Vec<String> = module
+        .names
+        .iter()
+        .map(|(id, name)| format!("\"{}\": {}", id, json_string(name)))
+        .collect();
+    writeln!(write, "  \"names\": {{ {} }},", names.join(", "))?;
+    let entry_points: Vec<String> = module
+        .module
+        .entry_points
+        .iter()
+        .filter_map(|inst| match inst.operands.as_slice() {
+            [
+                Operand::ExecutionModel(model),
+                Operand::IdRef(function),
+                Operand::LiteralString(name),
+                ..
+            ] => Some(format!(
+                "{{ \"function\": {}, \"name\": {}, \"execution_model\": {} }}",
+                function,
+                json_string(name),
+                json_string(&format!("{:?}", model))
+            )),
+            _ => None,
+        })
+        .collect();
+    writeln!(write, "  \"entry_points\": [")?;
+    write_list(write, "    ", &entry_points)?;
+    writeln!(write, "  ],")?;
+    writeln!(write, "  \"functions\": [")?;
+    let selected = functions.select(module, graph);
+    for (index, f) in selected.iter().enumerate() {
+        PetSpirv::new(module, f).write_json(write)?;
+        writeln!(
+            write,
+            "{}",
+            if index + 1 < selected.len() { "," } else { "" }
+        )?;
+    }
+    writeln!(write, "  ]")?;
+    writeln!(write, "}}")
+}
+
+/// Writes the items one per line, separated by commas.
+fn write_list(write: &mut impl Write, indent: &str, items: &[String]) -> io::Result<()> {
+    for (index, item) in items.iter().enumerate() {
+        let comma = if index + 1 < items.len() { "," } else { "" };
+        writeln!(write, "{}{}{}", indent, item, comma)?;
+    }
+    Ok(())
+}
+
+fn json_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn json_option(value: Option<impl ToString>) -> String {
+    value.map_or_else(|| "null".to_string(), |value| value.to_string())
+}
+
+fn json_float(value: f64) -> String {
+    if value.is_finite() {
+        format!("{:?}", value)
+    } else {
+        json_string(&format!("{}", value))
+    }
+}
+
+fn operand_json(operand: &Operand) -> String {
+    // The variant name, `IdRef(5)` becomes `IdRef`
+    let debug = format!("{:?}", operand);
+    let kind = debug.split('(').next().unwrap_or(&debug);
+    let value = match operand {
+        Operand::IdRef(id) | Operand::IdScope(id) | Operand::IdMemorySemantics(id) => {
+            id.to_string()
+        }
+        Operand::LiteralInt32(value) | Operand::LiteralExtInstInteger(value) => value.to_string(),
+        Operand::LiteralInt64(value) => value.to_string(),
+        Operand::LiteralFloat32(value) => json_float(f64::from(*value)),
+        Operand::LiteralFloat64(value) => json_float(*value),
+        Operand::LiteralString(value) => json_string(value),
+        operand => json_string(&operand.to_string()),
+    };
+    format!(
+        "{{ \"kind\": {}, \"value\": {} }}",
+        json_string(kind),
+        value
+    )
+}
+
+fn instruction_json(module: &SpirvModule, inst: &Instruction) -> String {
+    let operands: Vec<String> = operands(inst).iter().map(operand_json).collect();
+    format!(
+        "{{ \"opcode\": {}, \"result_type\": {}, \"result_id\": {}, \"operands\": [{}], \"text\": {} }}",
+        json_string(opname(inst)),
+        json_option(inst.result_type),
+        json_option(inst.result_id),
+        operands.join(", "),
+        json_string(&disassemble_text(module, inst))
+    )
+}
+
+fn edge_kind_json(kind: &EdgeKind) -> String {
+    match kind {
+        EdgeKind::Branch => "\"kind\": \"branch\"".to_string(),
+        EdgeKind::True { weight } => {
+            format!("\"kind\": \"true\", \"weight\": {}", json_option(*weight))
+        }
+        EdgeKind::False { weight } => {
+            format!("\"kind\": \"false\", \"weight\": {}", json_option(*weight))
+        }
+        EdgeKind::Case { values, default } => {
+            let values: Vec<String> = values
+                .iter()
+                .map(|value| json_string(&value.to_string()))
+                .collect();
+            format!(
+                "\"kind\": \"case\", \"values\": [{}], \"default\": {}",
+                values.join(", "),
+                default
+            )
+        }
+        EdgeKind::SelectionMerge => "\"kind\": \"selection_merge\"".to_string(),
+        EdgeKind::LoopMerge => "\"kind\": \"loop_merge\"".to_string(),
+        EdgeKind::Continue => "\"kind\": \"continue\"".to_string(),
+    }
+}
+
+impl<'spir> PetSpirv<'spir> {
+    /// Writes this function as a JSON object, without a trailing newline.
+    pub fn write_json(&self, write: &mut impl Write) -> io::Result<()> {
+        let reachable = self.reachable_blocks();
+        let name = self.module.get_name_fn(self.function);
+        writeln!(write, "    {{")?;
+        writeln!(write, "      \"id\": {},", self.function_id())?;
+        writeln!(
+            write,
+            "      \"name\": {},",
+            json_option(name.map(json_string))
+        )?;
+        writeln!(
+            write,
+            "      \"entry_block\": {},",
+            json_option(self.entry_block())
+        )?;
+        writeln!(write, "      \"blocks\": [")?;
+        let blocks: Vec<String> = self
+            .function
+            .blocks
+            .iter()
+            .filter_map(|block| {
+                let id = block.label.as_ref()?.result_id?;
+                let instructions: Vec<String> =
+                    block
+                    .instructions
+                    .iter()
+                    .map(|inst| instruction_json(self.module, inst))
+                    .collect();
+                Some(format!(
+                    "{{ \"id\": {}, \"name\": {}, \"reachable\": {}, \"color\": {}, \"instructions\": [\n          {}\n        ] }}",
+                    id,
+                    json_option(self.module.get_name_bb(block).map(json_string)),
+                    reachable.contains(&id),
+                    json_string(header_color(&Terminator::from_basic_block(block))),
+                    instructions.join(",\n          ")
+                ))
+            })
+            .collect();
+        write_list(write, "        ", &blocks)?;
+        writeln!(write, "      ],")?;
+        writeln!(write, "      \"edges\": [")?;
+        let mut edges = Vec::new();
+        for block in &self.function.blocks {
+            let source = match block.label.as_ref().and_then(|label| label.result_id) {
+                Some(id) => id,
+                None => continue,
+            };
+            let terminator = Terminator::from_basic_block(block);
+            for edge in terminator.edges() {
+                edges.push(format!(
+                    "{{ \"source\": {}, \"target\": {}, {}, \"label\": {} }}",
+                    source,
+                    edge.target,
+                    edge_kind_json(&edge.kind),
+                    json_option(
+                        self.edge_label(&terminator, &edge)
+                            .as_deref()
+                            .map(json_string)
+                    )
+                ));
+            }
+        }
+        write_list(write, "        ", &edges)?;
+        writeln!(write, "      ]")?;
+        write!(write, "    }}")
+    }
+}
@@ -0,0 +1,490 @@
+//! Layered graph layout in the style of Sugiyama et al.
+//!
+//! The graph is made acyclic by reversing the back edges of a depth first
+//! search, nodes are ranked by their longest path from a source, long edges are
+//! split by dummy nodes on every rank they cross, the order within ranks is
+//! found with barycenter sweeps and the horizontal coordinates are fitted to the
+//! neighbors of every node while keeping that order.
+use super::traversal::{DepthFirst, Event};
+use std::collections::HashSet;
+
+/// Vertical space between two ranks.
+const RANK_GAP: f64 = 50.0;
+/// Horizontal space between two nodes of a rank.
+const NODE_GAP: f64 = 30.0;
+/// Horizontal space next to an edge passing through a rank.
+const EDGE_GAP: f64 = 12.0;
+/// Room on the right of a node for an edge back to itself.
+const SELF_LOOP_WIDTH: f64 = 24.0;
+const ORDER_SWEEPS: usize = 8;
+const PLACEMENT_SWEEPS: usize = 8;
+
+/// Where the nodes and edges of a graph go.
+#[derive(Clone, Debug)]
+pub struct Layout {
+    /// The top left corner of every node
+    pub nodes: Vec<(f64, f64)>,
+    /// The points every edge passes, from its source to its target
+    pub edges: Vec<Vec<(f64, f64)>>,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// A node of the layered graph, either a node of the input or a dummy on a
+/// long edge.
+struct Vertex {
+    width: f64,
+    height: f64,
+    rank: usize,
+    dummy: bool,
+    upper: Vec<usize>,
+    lower: Vec<usize>,
+}
+
+/// Lays out the nodes with the given `(width, height)` sizes. Nodes without
+/// incoming edges are put on the top rank, earlier nodes are preferred as the
+/// roots of the depth first search that decides which edges point backwards.
+pub fn layout(sizes: &[(f64, f64)], edges: &[(usize, usize)]) -> Layout {
+    let n = sizes.len();
+    let reversed = reversed_edges(n, edges);
+    let self_loops: HashSet<usize> = edges
+        .iter()
+        .filter(|&&(source, target)| source == target)
+        .map(|&(source, _)| source)
+        .collect();
+
+    // Edges pointing downwards, self loops are drawn separately
+    let forward: Vec<Option<(usize, usize)>> = edges
+        .iter()
+        .enumerate()
+        .map(|(index, &(source, target))| {
+            if source == target {
+                None
+            } else if reversed.contains(&index) {
+                Some((target, source))
+            } else {
+                Some((source, target))
+            }
+        })
+        .collect();
+    let ranks = longest_path_ranks(n, forward.iter().flatten().cloned());
+
+    let mut vertices: Vec<Vertex> = sizes
+        .iter()
+        .enumerate()
+        .map(|(index, &(width, height))| Vertex {
+            width: if self_loops.contains(&index) {
+                width + SELF_LOOP_WIDTH
+            } else {
+                width
+            },
+            height,
+            rank: ranks[index],
+            dummy: false,
+            upper: Vec::new(),
+            lower: Vec::new(),
+        })
+        .collect();
+    // The vertices every edge passes from top to bottom
+    let mut chains: Vec<Vec<usize>> = Vec::with_capacity(edges.len());
+    for edge in &forward {
+        let (source, target) = match *edge {
+            Some(edge) => edge,
+            None => {
+                chains.push(Vec::new());
+                continue;
+            }
+        };
+        let mut chain = vec![source];
+        for rank in ranks[source] + 1..ranks[target] {
+            vertices.push(Vertex {
+                width: 0.0,
+                height: 0.0,
+                rank,
+                dummy: true,
+                upper: Vec::new(),
+                lower: Vec::new(),
+            });
+            chain.push(vertices.len() - 1);
+        }
+        chain.push(target);
+        for pair in chain.windows(2) {
+            vertices[pair[0]].lower.push(pair[1]);
+            vertices[pair[1]].upper.push(pair[0]);
+        }
+        chains.push(chain);
+    }
+
+    let rank_count = ranks.iter().max().map_or(0, |&max| max + 1);
+    let mut layers: Vec<Vec<usize>> = vec![Vec::new(); rank_count];
+    for (index, vertex) in vertices.iter().enumerate() {
+        layers[vertex.rank].push(index);
+    }
+    order_layers(&vertices, &mut layers);
+    let centers = place_layers(&vertices, &layers);
+
+    // Ranks are as high as their highest node, nodes are centered in them
+    let mut rank_tops = Vec::with_capacity(rank_count);
+    let mut rank_heights = Vec::with_capacity(rank_count);
+    let mut y = 0.0;
+    for layer in &layers {
+        let height = layer
+            .iter()
+            .map(|&index| vertices[index].height)
+            .fold(0.0, f64::max);
+        rank_tops.push(y);
+        rank_heights.push(height);
+        y += height + RANK_GAP;
+    }
+    let height = (y - RANK_GAP).max(0.0);
+    let left = (0..vertices.len())
+        .map(|index| centers[index] - vertices[index].width / 2.0)
+        .fold(f64::INFINITY, f64::min);
+    let left = if left.is_finite() { left } else { 0.0 };
+    let width = (0..vertices.len())
+        .map(|index| centers[index] + vertices[index].width / 2.0 - left)
+        .fold(0.0, f64::max);
+    let top = |index: usize| {
+        let vertex = &vertices[index];
+        rank_tops[vertex.rank] + (rank_heights[vertex.rank] - vertex.height) / 2.0
+    };
+    let nodes: Vec<(f64, f64)> = (0..n)
+        .map(|index| {
+            (
+                centers[index] - vertices[index].width / 2.0 - left,
+                top(index),
+            )
+        })
+        .collect();
+
+    // Spread the ends of the edges along the bottom and top of every node,
+    // ordered by where the other end goes so that they don't cross
+    let mut outgoing = vec![Vec::new(); n];
+    let mut incoming = vec![Vec::new(); n];
+    for (edge, chain) in chains.iter().enumerate() {
+        if let (Some(&upper), Some(&lower)) = (chain.first(), chain.last()) {
+            outgoing[upper].push((centers[chain[1]], edge));
+            incoming[lower].push((centers[chain[chain.len() - 2]], edge));
+        }
+    }
+    let mut ports = vec![(0.0, 0.0); chains.len()];
+    let spread = |node: usize, ends: &mut Vec<(f64, usize)>| -> Vec<(usize, f64)> {
+        ends.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        ends.iter()
+            .enumerate()
+            .map(|(i, &(_, edge))| {
+                let fraction = (i + 1) as f64 / (ends.len() + 1) as f64;
+                (edge, nodes[node].0 + sizes[node].0 * fraction)
+            })
+            .collect()
+    };
+    for node in 0..n {
+        for (edge, x) in spread(node, &mut outgoing[node]) {
+            ports[edge].0 = x;
+        }
+        for (edge, x) in spread(node, &mut incoming[node]) {
+            ports[edge].1 = x;
+        }
+    }
+
+    let edges = edges
+        .iter()
+        .enumerate()
+        .map(|(index, &(source, _))| {
+            let chain = &chains[index];
+            if chain.is_empty() {
+                // A loop on the right side of the node
+                let (x, y) = nodes[source];
+                let (width, height) = sizes[source];
+                let right = x + width;
+                return vec![
+                    (right, y + height * 0.3),
+                    (right + SELF_LOOP_WIDTH * 0.8, y + height * 0.3),
+                    (right + SELF_LOOP_WIDTH * 0.8, y + height * 0.7),
+                    (right, y + height * 0.7),
+                ];
+            }
+            let (upper, lower) = (chain[0], chain[chain.len() - 1]);
+            let mut points = vec![(ports[index].0, nodes[upper].1 + sizes[upper].1)];
+            for &dummy in &chain[1..chain.len() - 1] {
+                let x = centers[dummy] - left;
+                let rank = vertices[dummy].rank;
+                points.push((x, rank_tops[rank]));
+                points.push((x, rank_tops[rank] + rank_heights[rank]));
+            }
+            points.push((ports[index].1, nodes[lower].1));
+            if reversed.contains(&index) {
+                points.reverse();
+            }
+            points
+        })
+        .collect();
+
+    Layout {
+        nodes,
+        edges,
+        width,
+        height,
+    }
+}
+
+/// The indices of the edges that jump back to a node on the depth first search
+/// stack, self loops included. Parallel edges are either all reversed or none.
+fn reversed_edges(n: usize, edges: &[(usize, usize)]) -> HashSet<usize> {
+    let mut successors = vec![Vec::new(); n];
+    for &(source, target) in edges {
+        successors[source].push(target);
+    }
+    let back_edges: HashSet<(usize, usize)> = DepthFirst::new(&successors[..], (0..n).collect())
+        .filter_map(|event| match event {
+            Event::BackEdge(source, target) => Some((source, target)),
+            _ => None,
+        })
+        .collect();
+    (0..edges.len())
+        .filter(|&index| back_edges.contains(&edges[index]))
+        .collect()
+}
+
+/// Ranks every node one below its lowest predecessor in the acyclic graph.
+fn longest_path_ranks(n: usize, edges: impl Iterator<Item = (usize, usize)>) -> Vec<usize> {
+    let mut successors = vec![Vec::new(); n];
+    let mut in_degree = vec![0; n];
+    for (source, target) in edges {
+        successors[source].push(target);
+        in_degree[target] += 1;
+    }
+    let mut ranks = vec![0; n];
+    let mut ready: Vec<usize> = (0..n).rev().filter(|&node| in_degree[node] == 0).collect();
+    while let Some(node) = ready.pop() {
+        for &target in &successors[node] {
+            ranks[target] = ranks[target].max(ranks[node] + 1);
+            in_degree[target] -= 1;
+            if in_degree[target] == 0 {
+                ready.push(target);
+            }
+        }
+    }
+    ranks
+}
+
+/// Reorders every layer by the average position of the neighbors in the layer
+/// above, then in the layer below, and keeps the order with the fewest crossings.
+fn order_layers(vertices: &[Vertex], layers: &mut [Vec<usize>]) {
+    let mut position = vec![0; vertices.len()];
+    let index = |layers: &[Vec<usize>], position: &mut [usize]| {
+        for layer in layers {
+            for (i, &vertex) in layer.iter().enumerate() {
+                position[vertex] = i;
+            }
+        }
+    };
+    index(layers, &mut position);
+    let mut best = layers.to_vec();
+    let mut fewest = crossings(vertices, layers, &position);
+    for sweep in 0..ORDER_SWEEPS {
+        let downwards = sweep % 2 == 0;
+        let ranks: Vec<usize> = if downwards {
+            (1..layers.len()).collect()
+        } else {
+            (0..layers.len().saturating_sub(1)).rev().collect()
+        };
+        for rank in ranks {
+            let layer = &mut layers[rank];
+            let weight: Vec<(usize, f64)> = layer
+                .iter()
+                .map(|&vertex| {
+                    let neighbors = if downwards {
+                        &vertices[vertex].upper
+                    } else {
+                        &vertices[vertex].lower
+                    };
+                    let weight = if neighbors.is_empty() {
+                        position[vertex] as f64
+                    } else {
+                        neighbors
+                            .iter()
+                            .map(|&other| position[other] as f64)
+                            .sum::<f64>()
+                            / neighbors.len() as f64
+                    };
+                    (vertex, weight)
+                })
+                .collect();
+            let mut sorted = weight;
+            sorted.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+            for (i, &(vertex, _)) in sorted.iter().enumerate() {
+                layer[i] = vertex;
+                position[vertex] = i;
+            }
+        }
+        let count = crossings(vertices, layers, &position);
+        if count < fewest {
+            fewest = count;
+            best = layers.to_vec();
+        }
+    }
+    layers.clone_from_slice(&best);
+}
+
+/// The number of edge crossings between all pairs of neighboring layers.
+fn crossings(vertices: &[Vertex], layers: &[Vec<usize>], position: &[usize]) -> usize {
+    layers
+        .windows(2)
+        .map(|pair| {
+            let segments: Vec<(usize, usize)> = pair[0]
+                .iter()
+                .flat_map(|&upper| {
+                    vertices[upper]
+                        .lower
+                        .iter()
+                        .map(move |&lower| (position[upper], position[lower]))
+                })
+                .collect();
+            layer_crossings(&segments)
+        })
+        .sum()
+}
+
+/// The number of crossings between straight edges from one layer to the next,
+/// given as `(position in the upper layer, position in the lower layer)`.
+/// Edges sharing an end don't cross. Counted as inversions with a Fenwick tree.
+pub fn layer_crossings(segments: &[(usize, usize)]) -> usize {
+    let mut segments = segments.to_vec();
+    segments.sort();
+    let width = segments
+        .iter()
+        .map(|&(_, lower)| lower + 1)
+        .max()
+        .unwrap_or(0);
+    let mut tree = vec![0; width + 1];
+    let mut count = 0;
+    for (seen, &(_, lower)) in segments.iter().enumerate() {
+        // Segments seen so far that end right of this one
+        let mut at_most = 0;
+        let mut i = lower + 1;
+        while i > 0 {
+            at_most += tree[i];
+            i &= i - 1;
+        }
+        count += seen - at_most;
+        let mut i = lower + 1;
+        while i < tree.len() {
+            tree[i] += 1;
+            i += i & i.wrapping_neg();
+        }
+    }
+    count
+}
+
+/// The horizontal center of every vertex. Starting from the layers packed to
+/// the left, every layer is moved towards the neighbors above, then below,
+/// without changing its order.
+fn place_layers(vertices: &[Vertex], layers: &[Vec<usize>]) -> Vec<f64> {
+    let mut centers = vec![0.0; vertices.len()];
+    for layer in layers {
+        let separations = separations(vertices, layer);
+        let mut x = 0.0;
+        for (i, &vertex) in layer.iter().enumerate() {
+            centers[vertex] = x;
+            x += separations.get(i).cloned().unwrap_or(0.0);
+        }
+    }
+    for sweep in 0..PLACEMENT_SWEEPS {
+        let ranks: Vec<usize> = if sweep % 2 == 0 {
+            (1..layers.len()).collect()
+        } else {
+            (0..layers.len().saturating_sub(1)).rev().collect()
+        };
+        // The last sweep balances between the neighbors on both sides
+        let last = sweep + 1 == PLACEMENT_SWEEPS;
+        for rank in ranks {
+            let layer = &layers[rank];
+            let desired: Vec<f64> = layer
+                .iter()
+                .map(|&vertex| {
+                    let vertex_ref = &vertices[vertex];
+                    let neighbors: Vec<usize> = if last {
+                        vertex_ref
+                            .upper
+                            .iter()
+                            .chain(&vertex_ref.lower)
+                            .cloned()
+                            .collect()
+                    } else if sweep % 2 == 0 {
+                        vertex_ref.upper.clone()
+                    } else {
+                        vertex_ref.lower.clone()
+                    };
+                    if neighbors.is_empty() {
+                        centers[vertex]
+                    } else {
+                        neighbors.iter().map(|&other| centers[other]).sum::<f64>()
+                            / neighbors.len() as f64
+                    }
+                })
+                .collect();
+            // Dummies resist more so that long edges stay straight
+            let weights: Vec<f64> = layer
+                .iter()
+                .map(|&vertex| if vertices[vertex].dummy { 2.0 } else { 1.0 })
+                .collect();
+            let placed = fit_in_order(&desired, &weights, &separations(vertices, layer));
+            for (i, &vertex) in layer.iter().enumerate() {
+                centers[vertex] = placed[i];
+            }
+        }
+    }
+    centers
+}
+
+/// The minimal distance between the centers of every vertex of the layer and
+/// the next one.
+fn separations(vertices: &[Vertex], layer: &[usize]) -> Vec<f64> {
+    layer
+        .windows(2)
+        .map(|pair| {
+            let (a, b) = (&vertices[pair[0]], &vertices[pair[1]]);
+            let gap = if a.dummy || b.dummy {
+                EDGE_GAP
+            } else {
+                NODE_GAP
+            };
+            (a.width + b.width) / 2.0 + gap
+        })
+        .collect()
+}
+
+/// Positions as close as possible to `desired` in the weighted least squares
+/// sense, keeping every position at least `separations[i]` left of the next.
+///
+/// Subtracting the accumulated separations turns this into an isotonic
+/// regression, which is solved by pooling adjacent violators.
+fn fit_in_order(desired: &[f64], weights: &[f64], separations: &[f64]) -> Vec<f64> {
+    let mut offsets = Vec::with_capacity(desired.len());
+    let mut offset = 0.0;
+    for i in 0..desired.len() {
+        offsets.push(offset);
+        offset += separations.get(i).cloned().unwrap_or(0.0);
+    }
+    // Pools of (weighted sum, total weight, length)
+    let mut pools: Vec<(f64, f64, usize)> = Vec::new();
+    for i in 0..desired.len() {
+        let mut pool = ((desired[i] - offsets[i]) * weights[i], weights[i], 1);
+        while let Some(&last) = pools.last() {
+            if last.0 / last.1 < pool.0 / pool.1 {
+                break;
+            }
+            pools.pop();
+            pool = (pool.0 + last.0, pool.1 + last.1, pool.2 + last.2);
+        }
+        pools.push(pool);
+    }
+    let mut placed = Vec::with_capacity(desired.len());
+    for (sum, weight, length) in pools {
+        for _ in 0..length {
+            placed.push(sum / weight + offsets[placed.len()]);
+        }
+    }
+    placed
+}
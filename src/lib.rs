@@ -1,47 +1,113 @@
+extern crate regex;
 extern crate rspirv;
 extern crate spirv_headers as spirv;
 use rspirv::binary::Disassemble;
 use rspirv::dr::{Block, Function, Instruction, Module, Operand};
-use std::collections::{BTreeMap, HashSet};
-use std::fs::{read, File};
-use std::io::Write;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::read;
 use std::path::Path;
 
+mod callgraph;
+mod debuginfo;
+mod dominators;
+mod dot;
+mod error;
+mod extinst;
+mod filter;
+mod html;
+mod inline;
+mod json;
+mod layout;
+mod loader;
+mod loops;
+mod mermaid;
+mod source;
+mod svg;
+mod text;
+mod traversal;
+mod validate;
+
+pub use callgraph::{CallGraph, CallSite, EntryPoint};
+pub use debuginfo::{DebugInfo, SHADER_DEBUG_INFO};
+pub use dominators::{DominatorTree, DominatorTreeKind};
+pub use dot::{
+    export_dot, export_spirv_cfg, export_spirv_cfg_per_function, write_call_graph,
+    write_dominator_trees, write_spirv_cfg, DotOptions,
+};
+pub use error::CfgError;
+pub use filter::FunctionFilter;
+pub use html::{export_html, write_html};
+pub use json::{export_json, write_json, JSON_SCHEMA_VERSION};
+pub use layout::{layer_crossings, layout, Layout};
+use loader::ModuleLoader;
+pub use loops::{Loop, LoopInfo, LoopMismatch};
+pub use mermaid::{escape_mermaid, export_mermaid, write_mermaid, MermaidOptions};
+use source::ListingLine;
+pub use source::{Source, SourceLocation, SourceMap, SourceView};
+pub use svg::{export_svg, write_svg};
+pub use text::{export_text, write_text, TextOptions};
+pub use traversal::{BlockOrder, Postorder, Preorder};
+pub use validate::Violation;
+
+/// The value of operand `$index` of `$inst` if it's a `$name`, returns `None`
+/// from the enclosing function otherwise.
 macro_rules! extract {
-    ($val:expr, $name:path) => {
-        match $val {
-            $name(inner) => inner,
-            ref rest => panic!("Extract failed for {}, found {:?}", stringify!($name), rest),
+    ($inst:expr, $index:expr, $name:path) => {
+        match $inst.operands.get($index) {
+            Some(&$name(inner)) => inner,
+            _ => return None,
         }
     };
 }
 
-fn disassemble_inststruction(module: &SpirvModule, inst: &Instruction) -> String {
+/// The name of the opcode of an instruction without the `Op` prefix.
+fn opname(inst: &Instruction) -> &'static str {
+    if loader::is_terminate_invocation(inst) {
+        "TerminateInvocation"
+    } else {
+        inst.class.opname
+    }
+}
+
+/// The operands of an instruction as they appear in the binary.
+fn operands(inst: &Instruction) -> &[Operand] {
+    if loader::is_terminate_invocation(inst) {
+        &[]
+    } else {
+        &inst.operands
+    }
+}
+
+/// Disassembles an instruction with ids replaced by their names, unescaped.
+fn disassemble_text(module: &SpirvModule, inst: &Instruction) -> String {
+    if loader::is_terminate_invocation(inst) {
+        return format!("Op{}", opname(inst));
+    }
+    if let Some(text) = module.debug_info.disassemble(module, inst) {
+        return text;
+    }
+    if let Some(text) = extinst::disassemble(module, inst) {
+        return text;
+    }
     format!(
-        "{rid}{opcode}{rtype}{space}{operands}",
-        rid = {
-            escape_html(
-                &module
-                    .name_or_id(inst.result_id)
-                    .map(|name| format!("{} = ", name))
-                    .unwrap_or_else(String::new),
-            )
-        },
-        opcode = format!("Op{}", inst.class.opname),
+        "{rid}Op{opcode}{rtype}{space}{operands}",
+        rid = module
+            .name_or_id(inst.result_id)
+            .map(|name| format!("{} = ", name))
+            .unwrap_or_default(),
+        opcode = inst.class.opname,
         // extra space both before and after the reseult type
-        rtype = escape_html(
-            &module
-                .name_or_id(inst.result_type)
-                .map(|name| format!(" {}", name))
-                .unwrap_or_else(String::new)
-        ),
+        rtype = inst
+            .result_type
+            .map(|id| format!(" {}", module.operand_name(id)))
+            .unwrap_or_default(),
         //rtype = "",
         space = if !inst.operands.is_empty() { " " } else { "" },
         operands = {
             inst.operands
                 .iter()
                 .map(|operand| match *operand {
-                    Operand::IdRef(id) => escape_html(&module.name_or_id(Some(id)).unwrap()),
+                    Operand::IdRef(id) => module.operand_name(id),
                     _ => operand.disassemble(),
                 })
                 .collect::<Vec<String>>()
@@ -58,6 +124,13 @@ pub fn escape_html(s: &str) -> String {
 pub struct SpirvModule {
     pub module: Module,
     pub names: BTreeMap<u32, String>,
+    /// Line information, only available for modules parsed from a binary
+    pub source_map: SourceMap,
+    /// The decoded `NonSemantic.Shader.DebugInfo.100` instructions
+    pub debug_info: DebugInfo,
+    /// Types and constants shown in the disassembly instead of their ids, see
+    /// `inline_types` and `inline_constants`
+    pub inlined: HashMap<u32, String>,
 }
 impl SpirvModule {
     pub fn name_or_id(&self, id: Option<spirv::Word>) -> Option<String> {
@@ -69,6 +142,14 @@ impl SpirvModule {
                 .unwrap_or_else(|| format!("%{}", id)),
         )
     }
+    /// How an operand shows up in the disassembly: its type or value if it's
+    /// inlined, its name otherwise.
+    pub fn operand_name(&self, id: spirv::Word) -> String {
+        match self.inlined.get(&id) {
+            Some(inlined) => inlined.clone(),
+            None => self.name_or_id(Some(id)).expect("name"),
+        }
+    }
     pub fn get_name_fn<'module>(&'module self, f: &Function) -> Option<&'module str> {
         let id = f.def.as_ref()?.result_id?;
         self.names.get(&id).map(String::as_str)
@@ -78,34 +159,103 @@ impl SpirvModule {
         let return_id = label.result_id?;
         self.names.get(&return_id).map(String::as_str)
     }
+    /// Loads a module from a `.spv` file, panicking if it can't be read or parsed.
+    ///
+    /// Use `try_load` to handle the error instead.
     pub fn load<P: AsRef<Path>>(p: &P) -> Self {
-        fn inner(p: &Path) -> SpirvModule {
-            use rspirv::binary::Parser;
-            use rspirv::dr::Loader;
-            let module = {
-                let spv_file = read(p).expect("file");
-                let mut loader = Loader::new();
-                {
-                    let p = Parser::new(&spv_file, &mut loader);
-                    p.parse().expect("parse")
-                };
-                loader.module()
-            };
-            let names = module
-                .debugs
-                .iter()
-                .filter_map(|inst| match inst.class.opcode {
-                    spirv::Op::Name => {
-                        let id = extract!(inst.operands[0], Operand::IdRef);
-                        let name = extract!(&inst.operands[1], Operand::LiteralString);
-                        Some((id, name.clone()))
-                    }
-                    _ => None,
-                })
+        Self::try_load(p).unwrap_or_else(|err| panic!("{}: {}", p.as_ref().display(), err))
+    }
+    /// Loads a module from a `.spv` file.
+    pub fn try_load<P: AsRef<Path>>(p: &P) -> Result<Self, CfgError> {
+        let bytes = read(p)?;
+        Self::from_bytes(&bytes)
+    }
+    /// Parses a module from the bytes of a SPIR-V binary. Both little and big
+    /// endian binaries are accepted, the byte order is detected from the magic number.
+    /// A length that isn't a multiple of four is a parse error at the last,
+    /// incomplete word.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CfgError> {
+        if bytes.len() >= 4 && loader::read_word(bytes, 0) == spirv::MAGIC_NUMBER.swap_bytes() {
+            let swapped: Vec<u8> = bytes
+                .chunks(4)
+                .flat_map(|word| word.iter().rev().cloned())
                 .collect();
-            SpirvModule { names, module }
+            return Self::from_bytes(&swapped);
+        }
+        loader::check_header(bytes)?;
+        if let Some(padded) = loader::pad_wide_switches(bytes) {
+            return Self::replace_and_parse(&padded);
         }
-        inner(p.as_ref())
+        Self::replace_and_parse(bytes)
+    }
+    fn replace_and_parse(bytes: &[u8]) -> Result<Self, CfgError> {
+        match loader::replace_terminate_invocations(bytes) {
+            Some((replaced, terminate_invocations)) => {
+                Self::parse(&replaced, terminate_invocations)
+            }
+            None => Self::parse(bytes, HashSet::new()),
+        }
+    }
+    fn parse(bytes: &[u8], terminate_invocations: HashSet<usize>) -> Result<Self, CfgError> {
+        use rspirv::binary::Parser;
+        let mut loader = ModuleLoader::new(terminate_invocations);
+        if let Err(state) = Parser::new(bytes, &mut loader).parse() {
+            let word_offset = loader::error_word_offset(bytes, &state, loader.consumed());
+            return Err(CfgError::Parse { state, word_offset });
+        }
+        let (module, locations) = loader.into_parts();
+        Self::with_locations(module, locations)
+    }
+    /// Parses a module from SPIR-V words in native byte order.
+    pub fn from_words(words: &[u32]) -> Result<Self, CfgError> {
+        let bytes: Vec<u8> = words
+            .iter()
+            .flat_map(|word| word.to_le_bytes().to_vec())
+            .collect();
+        Self::from_bytes(&bytes)
+    }
+    /// Wraps a module that has already been loaded or built with rspirv.
+    ///
+    /// rspirv doesn't keep `OpLine` instructions, so only the `DebugLine`s of
+    /// the shader debug info end up in the source map of the module.
+    pub fn from_module(module: Module) -> Result<Self, CfgError> {
+        Self::with_locations(module, HashMap::new())
+    }
+    fn with_locations(
+        module: Module,
+        locations: HashMap<spirv::Word, Vec<Option<SourceLocation>>>,
+    ) -> Result<Self, CfgError> {
+        let mut names = BTreeMap::new();
+        for inst in &module.debugs {
+            if inst.class.opcode != spirv::Op::Name {
+                continue;
+            }
+            match inst.operands.as_slice() {
+                [Operand::IdRef(id), Operand::LiteralString(name)] => {
+                    names.insert(*id, name.clone());
+                }
+                _ => return Err(CfgError::MalformedDebugInstruction(inst.clone())),
+            }
+        }
+        let debug_info = DebugInfo::new(&module);
+        // The debug info only names what OpName doesn't
+        for (id, name) in debug_info
+            .local_variables
+            .iter()
+            .chain(&debug_info.function_names)
+        {
+            names.entry(*id).or_insert_with(|| name.clone());
+        }
+        let mut source_map = SourceMap::new(&module.debugs)?;
+        source_map.set_locations(locations);
+        source_map.add_debug_info(&module, &debug_info);
+        Ok(SpirvModule {
+            names,
+            module,
+            source_map,
+            debug_info,
+            inlined: HashMap::new(),
+        })
     }
 }
 
@@ -115,100 +265,189 @@ pub struct PetSpirv<'spir> {
     pub block_map: BTreeMap<u32, &'spir Block>,
 }
 
-pub fn export_spirv_cfg(module: &SpirvModule) {
-    let mut file = File::create("test.dot").expect("file");
-    for f in &module.module.functions {
-        let s = PetSpirv::new(module, f);
-        s.add_fn_to_dot(&mut file);
+/// The structured control flow declaration that precedes a header block's terminator.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Merge {
+    /// `OpSelectionMerge`
+    Selection { merge_block: spirv::Word },
+    /// `OpLoopMerge`, the block is a loop header
+    Loop {
+        merge_block: spirv::Word,
+        continue_target: spirv::Word,
+    },
+}
+
+impl Merge {
+    pub fn merge_block(&self) -> spirv::Word {
+        match *self {
+            Merge::Selection { merge_block } | Merge::Loop { merge_block, .. } => merge_block,
+        }
+    }
+    pub fn continue_target(&self) -> Option<spirv::Word> {
+        match *self {
+            Merge::Loop {
+                continue_target, ..
+            } => Some(continue_target),
+            Merge::Selection { .. } => None,
+        }
     }
 }
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum Terminator {
     Branch {
+        merge: Option<Merge>,
         target: spirv::Word,
     },
     BranchConditional {
-        merge_block: Option<spirv::Word>,
+        merge: Option<Merge>,
+        condition: spirv::Word,
         true_block: spirv::Word,
         false_block: spirv::Word,
+        /// The optional branch weights of the true and false block
+        weights: Option<(u32, u32)>,
     },
     Switch {
-        merge_block: Option<spirv::Word>,
-        values: Vec<spirv::Word>,
-        targets: Vec<spirv::Word>,
+        merge: Option<Merge>,
+        selector: spirv::Word,
+        default: spirv::Word,
+        /// The literal and target of every case in declaration order
+        cases: Vec<(u64, spirv::Word)>,
     },
-    /// Indicates that the control flow has stopped
-    End,
+    /// `OpReturn`
+    Return,
+    /// `OpReturnValue`
+    ReturnValue { value: spirv::Word },
+    /// `OpKill`, the fragment is discarded
+    Kill,
+    /// `OpTerminateInvocation`, which replaces `OpKill` as the lowering of
+    /// `discard` since SPIR-V 1.6
+    TerminateInvocation,
+    /// `OpUnreachable`
+    Unreachable,
+    /// `OpTerminateRayKHR`, which spirv_headers 1.5 names `TerminateRayNV`
+    /// as both share opcode 5336
+    TerminateRay,
+    /// `OpIgnoreIntersectionKHR`, which spirv_headers 1.5 names
+    /// `IgnoreIntersectionNV` as both share opcode 5335
+    IgnoreIntersection,
+    /// The block is empty or doesn't end in a terminator instruction
+    Missing,
 }
 
 impl Terminator {
-    pub fn merge_block(&self) -> Option<spirv::Word> {
+    pub fn merge(&self) -> Option<Merge> {
         match self {
-            Terminator::Switch { merge_block, .. }
-            | Terminator::BranchConditional { merge_block, .. } => *merge_block,
+            Terminator::Branch { merge, .. }
+            | Terminator::Switch { merge, .. }
+            | Terminator::BranchConditional { merge, .. } => *merge,
             _ => None,
         }
     }
+    /// Whether control flow leaves the function (or invocation) at this terminator.
+    pub fn is_exit(&self) -> bool {
+        !matches!(
+            self,
+            Terminator::Branch { .. }
+                | Terminator::BranchConditional { .. }
+                | Terminator::Switch { .. }
+        )
+    }
+    pub fn merge_block(&self) -> Option<spirv::Word> {
+        self.merge().map(|merge| merge.merge_block())
+    }
+    pub fn continue_target(&self) -> Option<spirv::Word> {
+        self.merge()?.continue_target()
+    }
+    /// The terminator of a block, `Missing` if the block doesn't end in one or
+    /// its operands (or those of the merge instruction) are malformed.
     pub fn from_basic_block(bb: &Block) -> Terminator {
-        let get_merge_block = || -> Option<spirv::Word> {
-            let before_last = bb.instructions.get(bb.instructions.len() - 2)?;
-            match before_last.class.opcode {
-                spirv::Op::SelectionMerge => {
-                    Some(extract!(before_last.operands[0], Operand::IdRef))
-                }
-                _ => None,
+        Self::parse(bb).unwrap_or(Terminator::Missing)
+    }
+    fn parse(bb: &Block) -> Option<Terminator> {
+        let merge = match bb
+            .instructions
+            .len()
+            .checked_sub(2)
+            .and_then(|index| bb.instructions.get(index))
+        {
+            Some(inst) if inst.class.opcode == spirv::Op::SelectionMerge => {
+                Some(Merge::Selection {
+                    merge_block: extract!(inst, 0, Operand::IdRef),
+                })
             }
+            Some(inst) if inst.class.opcode == spirv::Op::LoopMerge => Some(Merge::Loop {
+                merge_block: extract!(inst, 0, Operand::IdRef),
+                continue_target: extract!(inst, 1, Operand::IdRef),
+            }),
+            _ => None,
         };
-        let inst = if let Some(inst) = bb.instructions.last() {
-            inst
-        } else {
-            return Terminator::End;
-        };
-        match inst.class.opcode {
+        let inst = bb.instructions.last()?;
+        Some(match inst.class.opcode {
             spirv::Op::Switch => {
-                let default = extract!(inst.operands[1], Operand::IdRef);
-                let merge_block = get_merge_block();
-                let values: Vec<u32> = inst
-                    .operands
-                    .iter()
-                    .skip(2)
-                    .step_by(2)
-                    .map(|operand| *extract!(operand, Operand::LiteralInt32))
-                    .collect();
-                let mut targets: Vec<spirv::Word> = inst
-                    .operands
-                    .iter()
-                    .skip(3)
-                    .step_by(2)
-                    .map(|operand| *extract!(operand, Operand::IdRef))
-                    .collect();
-                targets.push(default);
+                let selector = extract!(inst, 0, Operand::IdRef);
+                let default = extract!(inst, 1, Operand::IdRef);
+                let mut cases = Vec::new();
+                for case in inst.operands.get(2..)?.chunks(2) {
+                    let value = match case[0] {
+                        Operand::LiteralInt32(value) => u64::from(value),
+                        Operand::LiteralInt64(value) => value,
+                        _ => return None,
+                    };
+                    match case.get(1) {
+                        Some(&Operand::IdRef(target)) => cases.push((value, target)),
+                        _ => return None,
+                    }
+                }
                 Terminator::Switch {
-                    merge_block,
-                    values,
-                    targets,
+                    merge,
+                    selector,
+                    default,
+                    cases,
                 }
             }
             spirv::Op::BranchConditional => {
-                let merge_block = get_merge_block();
-                let true_block = extract!(inst.operands[1], Operand::IdRef);
-                let false_block = extract!(inst.operands[2], Operand::IdRef);
+                let condition = extract!(inst, 0, Operand::IdRef);
+                let true_block = extract!(inst, 1, Operand::IdRef);
+                let false_block = extract!(inst, 2, Operand::IdRef);
+                let weights = match inst.operands.get(3..5) {
+                    Some([Operand::LiteralInt32(t), Operand::LiteralInt32(f)]) => Some((*t, *f)),
+                    _ => None,
+                };
                 Terminator::BranchConditional {
-                    merge_block,
+                    merge,
+                    condition,
                     true_block,
                     false_block,
+                    weights,
                 }
             }
-            spirv::Op::Branch => {
-                let target = extract!(inst.operands[0], Operand::IdRef);
-                Terminator::Branch { target }
+            spirv::Op::Branch => Terminator::Branch {
+                merge,
+                target: extract!(inst, 0, Operand::IdRef),
+            },
+            spirv::Op::Return => Terminator::Return,
+            spirv::Op::ReturnValue => Terminator::ReturnValue {
+                value: extract!(inst, 0, Operand::IdRef),
+            },
+            spirv::Op::Kill if loader::is_terminate_invocation(inst) => {
+                Terminator::TerminateInvocation
             }
-            _ => Terminator::End,
-        }
+            spirv::Op::Kill => Terminator::Kill,
+            spirv::Op::Unreachable => Terminator::Unreachable,
+            spirv::Op::TerminateRayNV => Terminator::TerminateRay,
+            spirv::Op::IgnoreIntersectionNV => Terminator::IgnoreIntersection,
+            _ => Terminator::Missing,
+        })
     }
     pub fn successors(&self) -> impl Iterator<Item = spirv::Word> {
         match self {
-            Terminator::Switch { ref targets, .. } => targets.clone(),
-            Terminator::Branch { target } => vec![*target],
+            Terminator::Switch { default, cases, .. } => cases
+                .iter()
+                .map(|&(_, target)| target)
+                .chain(Some(*default))
+                .collect(),
+            Terminator::Branch { target, .. } => vec![*target],
             Terminator::BranchConditional {
                 true_block,
                 false_block,
@@ -218,67 +457,192 @@ impl Terminator {
         }
         .into_iter()
     }
+    /// All outgoing edges of the block including the merge and continue
+    /// declarations. Switch cases that share a target become a single edge.
+    pub fn edges(&self) -> Vec<Edge> {
+        let mut edges = Vec::new();
+        match self.merge() {
+            Some(Merge::Selection { merge_block }) => edges.push(Edge {
+                target: merge_block,
+                kind: EdgeKind::SelectionMerge,
+            }),
+            Some(Merge::Loop {
+                merge_block,
+                continue_target,
+            }) => {
+                edges.push(Edge {
+                    target: merge_block,
+                    kind: EdgeKind::LoopMerge,
+                });
+                edges.push(Edge {
+                    target: continue_target,
+                    kind: EdgeKind::Continue,
+                });
+            }
+            None => {}
+        }
+        match self {
+            Terminator::Switch { default, cases, .. } => {
+                let mut grouped: Vec<(spirv::Word, Vec<u64>)> = Vec::new();
+                for &(value, target) in cases {
+                    match grouped.iter_mut().find(|group| group.0 == target) {
+                        Some(group) => group.1.push(value),
+                        None => grouped.push((target, vec![value])),
+                    }
+                }
+                if !grouped.iter().any(|group| group.0 == *default) {
+                    grouped.push((*default, Vec::new()));
+                }
+                edges.extend(grouped.into_iter().map(|(target, values)| Edge {
+                    target,
+                    kind: EdgeKind::Case {
+                        values,
+                        default: target == *default,
+                    },
+                }));
+            }
+            Terminator::BranchConditional {
+                true_block,
+                false_block,
+                weights,
+                ..
+            } => {
+                edges.push(Edge {
+                    target: *true_block,
+                    kind: EdgeKind::True {
+                        weight: weights.map(|weights| weights.0),
+                    },
+                });
+                edges.push(Edge {
+                    target: *false_block,
+                    kind: EdgeKind::False {
+                        weight: weights.map(|weights| weights.1),
+                    },
+                });
+            }
+            _ => edges.extend(self.successors().map(|target| Edge {
+                target,
+                kind: EdgeKind::Branch,
+            })),
+        }
+        edges
+    }
 }
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EdgeKind {
+    Branch,
+    /// The edge taken when the condition of `OpBranchConditional` holds
+    True {
+        weight: Option<u32>,
+    },
+    False {
+        weight: Option<u32>,
+    },
+    /// Switch cases jumping to the same block, `default` is set if the block is
+    /// also the default target
+    Case {
+        values: Vec<u64>,
+        default: bool,
+    },
+    SelectionMerge,
+    LoopMerge,
+    Continue,
+}
+
+impl EdgeKind {
+    /// A short description of the edge, e.g. `case 1, 2` for switch cases.
+    pub fn label(&self) -> Option<String> {
+        match self {
+            EdgeKind::Case { values, default } => {
+                let mut labels: Vec<String> = values.iter().map(u64::to_string).collect();
+                if *default {
+                    labels.push("default".to_string());
+                }
+                if values.is_empty() {
+                    Some(labels.join(", "))
+                } else {
+                    Some(format!("case {}", labels.join(", ")))
+                }
+            }
+            EdgeKind::True { weight } | EdgeKind::False { weight } => {
+                let branch = if let EdgeKind::True { .. } = self {
+                    "true"
+                } else {
+                    "false"
+                };
+                Some(match weight {
+                    Some(weight) => format!("{} (weight {})", branch, weight),
+                    None => branch.to_string(),
+                })
+            }
+            EdgeKind::LoopMerge => Some("merge".to_string()),
+            EdgeKind::Continue => Some("continue".to_string()),
+            EdgeKind::Branch | EdgeKind::SelectionMerge => None,
+        }
+    }
+}
+
+/// An outgoing edge of a block.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Edge {
+    pub target: spirv::Word,
+    pub kind: EdgeKind,
+}
+
 impl<'spir> PetSpirv<'spir> {
     pub fn get_block(&self, id: u32) -> &'spir Block {
         self.block_map
             .get(&id)
             .unwrap_or_else(|| panic!("Block {}", id))
     }
-    pub fn add_fn_to_dot(&self, write: &mut impl Write) {
-        let fn_name = self.module.get_name_fn(&self.function).unwrap_or("Unknown");
-        writeln!(write, "digraph {{").unwrap();
-        writeln!(write, "graph [fontname=\"monospace\"];").unwrap();
-        writeln!(write, "node [fontname=\"monospace\"];").unwrap();
-        writeln!(write, "edge [fontname=\"monospace\"];").unwrap();
-        let fn_id = self.function.def.as_ref().unwrap().result_id.unwrap();
-        let entry = self.function.blocks[0]
-            .label
+    pub fn function_id(&self) -> spirv::Word {
+        self.function
+            .def
             .as_ref()
-            .expect("label")
-            .result_id
-            .expect("result id");
-        writeln!(
-            write,
-            "{id} [shape=\"box\", label={name:?}];",
-            id = fn_id,
-            name = fn_name
-        )
-        .unwrap();
-        writeln!(write, "{} -> {}", fn_id, entry).unwrap();
+            .and_then(|def| def.result_id)
+            .expect("function result id")
+    }
 
-        for (id, block) in &self.block_map {
-            let name = self.module.name_or_id(Some(*id)).expect("name");
-            writeln!(write, "  {id} [shape=none, label=<", id = id,).unwrap();
-            writeln!(write, "\t<table>").unwrap();
-            writeln!(
-                write,
-                "\t\t<tr><td align=\"center\" bgcolor=\"gray\" colspan=\"1\">{name}</td></tr>",
-                name = name
-            )
-            .unwrap();
-            writeln!(write, "\t\t<tr><td align=\"left\" balign=\"left\">").unwrap();
-            for inst in &block.instructions {
-                writeln!(
-                    write,
-                    "\t\t\t{}<br/>",
-                    disassemble_inststruction(&self.module, inst)
-                )
-                .unwrap();
+    /// The id of the first block, `None` for function declarations without a body.
+    pub fn entry_block(&self) -> Option<spirv::Word> {
+        self.function.blocks.first()?.label.as_ref()?.result_id
+    }
+
+    /// The distinct successors of block `id` that belong to this function.
+    pub fn successors(&self, id: spirv::Word) -> Vec<spirv::Word> {
+        let mut successors = Vec::new();
+        for target in Terminator::from_basic_block(self.get_block(id)).successors() {
+            if self.block_map.contains_key(&target) && !successors.contains(&target) {
+                successors.push(target);
             }
-            writeln!(write, "\t</td></tr></table>>];").unwrap();
         }
+        successors
+    }
 
-        self.traverse(|node, _| {
-            let terminator = Terminator::from_basic_block(self.get_block(node));
-            if let Some(merge_block) = terminator.merge_block() {
-                writeln!(write, "\t{} -> {}[style=\"dashed\"]", node, merge_block).unwrap();
-            }
-            for bb in terminator.successors() {
-                writeln!(write, "  {node} -> {target}", node = node, target = bb).unwrap();
+    /// The blocks that can be reached from the entry block.
+    pub fn reachable_blocks(&self) -> HashSet<spirv::Word> {
+        self.preorder().collect()
+    }
+
+    /// The label of an edge, conditional branches name their condition.
+    pub fn edge_label(&self, terminator: &Terminator, edge: &Edge) -> Option<String> {
+        match (&edge.kind, terminator) {
+            (EdgeKind::True { weight }, Terminator::BranchConditional { condition, .. })
+            | (EdgeKind::False { weight }, Terminator::BranchConditional { condition, .. }) => {
+                let branch = if let EdgeKind::True { .. } = edge.kind {
+                    "true"
+                } else {
+                    "false"
+                };
+                let condition = self.module.operand_name(*condition);
+                Some(match weight {
+                    Some(weight) => format!("{}: {} (weight {})", branch, condition, weight),
+                    None => format!("{}: {}", branch, condition),
+                })
             }
-        });
-        writeln!(write, "}}").unwrap();
+            _ => edge.kind.label(),
+        }
     }
 
     pub fn get_label(&self, id: u32) -> String {
@@ -289,29 +653,10 @@ impl<'spir> PetSpirv<'spir> {
             .unwrap_or(format!("{}", id))
     }
 
+    /// Calls `f` for every reachable block in depth first preorder.
     pub fn traverse(&self, mut f: impl FnMut(u32, &Terminator)) {
-        let mut map = HashSet::new();
-        if let Some(start_block) = self.function.blocks.first() {
-            let label = start_block.label.as_ref().unwrap();
-            let id = label.result_id.unwrap();
-            self.traverse_from(&mut map, id, &mut f);
-        }
-    }
-
-    fn traverse_from(
-        &self,
-        visited: &mut HashSet<u32>,
-        root_id: u32,
-        f: &mut impl FnMut(u32, &Terminator),
-    ) {
-        visited.insert(root_id);
-        let root = self.get_block(root_id);
-        let terminator = Terminator::from_basic_block(root);
-        f(root_id, &terminator);
-        for bb in terminator.successors() {
-            if !visited.contains(&bb) {
-                self.traverse_from(visited, bb, f);
-            }
+        for id in self.preorder() {
+            f(id, &Terminator::from_basic_block(self.get_block(id)));
         }
     }
 
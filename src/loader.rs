@@ -0,0 +1,445 @@
+//! Turns the instruction stream of the rspirv parser into a `dr::Module`.
+//!
+//! This mirrors `rspirv::dr::Loader`, which doesn't know about the ray tracing
+//! terminators and would reject any function using them. The byte pre-passes
+//! make binaries parseable that rspirv would reject or misread: 64 bit switch
+//! cases and `OpTerminateInvocation`.
+use error::CfgError;
+use rspirv::binary::{Consumer, DecodeError, ParseAction, ParseState};
+use rspirv::dr::{Block, Error, Function, Instruction, Module, ModuleHeader, Operand};
+use rspirv::grammar::{reflect, CoreInstructionTable, OperandKind};
+use source::SourceLocation;
+use spirv::Word;
+use std::collections::{HashMap, HashSet};
+
+const HEADER_WORDS: usize = 5;
+
+/// The opcode of `OpTerminateInvocation`, which came with SPIR-V 1.6 and the
+/// `SPV_KHR_terminate_invocation` extension and is unknown to rspirv 0.7.
+pub(crate) const TERMINATE_INVOCATION: u16 = 4416;
+
+pub(crate) fn check_header(bytes: &[u8]) -> Result<(), CfgError> {
+    if bytes.len() < HEADER_WORDS * 4 {
+        return Err(CfgError::HeaderTruncated { len: bytes.len() });
+    }
+    let magic = read_word(bytes, 0);
+    if magic != spirv::MAGIC_NUMBER {
+        return Err(CfgError::BadMagic(magic));
+    }
+    let version = read_word(bytes, 1);
+    let major = (version >> 16) as u8;
+    let minor = (version >> 8) as u8;
+    if major != spirv::MAJOR_VERSION || minor > spirv::MINOR_VERSION {
+        return Err(CfgError::UnsupportedVersion { major, minor });
+    }
+    // The parser would silently drop an incomplete last word
+    let trailing = bytes.len() % 4;
+    if trailing != 0 {
+        let offset = bytes.len() - trailing;
+        return Err(CfgError::Parse {
+            state: ParseState::OperandError(DecodeError::StreamExpected(offset)),
+            word_offset: Some(offset / 4),
+        });
+    }
+    Ok(())
+}
+
+pub(crate) fn read_word(bytes: &[u8], index: usize) -> u32 {
+    let b = &bytes[index * 4..index * 4 + 4];
+    u32::from(b[0]) | u32::from(b[1]) << 8 | u32::from(b[2]) << 16 | u32::from(b[3]) << 24
+}
+
+/// Finds the word offset of the instruction a parse error refers to.
+///
+/// Most parser states carry a byte offset, the rest (operand decoding and
+/// loader errors) are located by walking the instruction stream up to the
+/// instruction that was being processed.
+pub(crate) fn error_word_offset(
+    bytes: &[u8],
+    state: &ParseState,
+    consumed: usize,
+) -> Option<usize> {
+    match *state {
+        ParseState::WordCountZero(offset, _)
+        | ParseState::OpcodeUnknown(offset, _, _)
+        | ParseState::OperandExpected(offset, _)
+        | ParseState::OperandExceeded(offset, _)
+        | ParseState::TypeUnsupported(offset, _)
+        | ParseState::SpecConstantOpIntegerIncorrect(offset, _) => Some(offset / 4),
+        // The loader fails on the instruction it was just handed.
+        ParseState::ConsumerError(_) => instruction_word_offset(bytes, consumed.checked_sub(1)?),
+        ParseState::OperandError(_) => instruction_word_offset(bytes, consumed),
+        _ => None,
+    }
+}
+
+/// Returns the word offset of the instruction with the given zero based index.
+fn instruction_word_offset(bytes: &[u8], index: usize) -> Option<usize> {
+    let len = bytes.len() / 4;
+    let mut offset = HEADER_WORDS;
+    for _ in 0..index {
+        let word_count = (read_word(bytes, offset) >> 16) as usize;
+        if word_count == 0 {
+            return None;
+        }
+        offset += word_count;
+        if offset >= len {
+            return None;
+        }
+    }
+    Some(offset)
+}
+
+/// The word offset and word count of every instruction after the header, up
+/// to the first one with a bad word count.
+fn instructions(bytes: &[u8]) -> impl Iterator<Item = (usize, usize)> + '_ {
+    let len = bytes.len() / 4;
+    let mut offset = HEADER_WORDS;
+    std::iter::from_fn(move || {
+        if offset >= len {
+            return None;
+        }
+        let word_count = (read_word(bytes, offset) >> 16) as usize;
+        if word_count == 0 || offset + word_count > len {
+            return None;
+        }
+        let start = offset;
+        offset += word_count;
+        Some((start, word_count))
+    })
+}
+
+/// Makes `OpSwitch` instructions with a 64 bit selector and an odd number of
+/// cases parseable by appending a zero word. rspirv reads the cases as pairs of
+/// words, so it would run out of words in the middle of the last pair.
+/// `ModuleLoader` drops the padding again when it regroups the case words.
+///
+/// Returns `None` when the binary doesn't need any padding.
+pub(crate) fn pad_wide_switches(bytes: &[u8]) -> Option<Vec<u8>> {
+    let instructions = || instructions(bytes);
+    let opcode = |offset: usize| (read_word(bytes, offset) & 0xffff) as u16;
+    let has_int64 = instructions().any(|(offset, word_count)| {
+        opcode(offset) == spirv::Op::TypeInt as u16
+            && word_count > 2
+            && read_word(bytes, offset + 2) == 64
+    });
+    if !has_int64 {
+        return None;
+    }
+
+    let mut has_result_type = HashMap::new();
+    let mut int_widths = HashMap::new();
+    let mut value_types = HashMap::new();
+    let mut padded = Vec::new();
+    for (offset, word_count) in instructions() {
+        let op = opcode(offset);
+        let typed = *has_result_type.entry(op).or_insert_with(|| {
+            CoreInstructionTable::lookup_opcode(op).is_some_and(|grammar| {
+                grammar.operands.len() > 1
+                    && grammar.operands[0].kind == OperandKind::IdResultType
+                    && grammar.operands[1].kind == OperandKind::IdResult
+            })
+        });
+        if typed && word_count > 2 {
+            value_types.insert(read_word(bytes, offset + 2), read_word(bytes, offset + 1));
+        }
+        if op == spirv::Op::TypeInt as u16 && word_count > 2 {
+            int_widths.insert(read_word(bytes, offset + 1), read_word(bytes, offset + 2));
+        }
+        if op == spirv::Op::Switch as u16 && word_count >= 3 {
+            let selector = read_word(bytes, offset + 1);
+            let width = value_types.get(&selector).and_then(|ty| int_widths.get(ty));
+            let case_words = word_count - 3;
+            if width == Some(&64) && case_words % 3 == 0 && (case_words / 3) % 2 == 1 {
+                padded.push(offset);
+            }
+        }
+    }
+    if padded.is_empty() {
+        return None;
+    }
+
+    let mut out = Vec::with_capacity(bytes.len() + padded.len() * 4);
+    let mut copied = 0;
+    for offset in padded {
+        let header = read_word(bytes, offset) + (1 << 16);
+        let end = offset + (read_word(bytes, offset) >> 16) as usize;
+        out.extend_from_slice(&bytes[copied * 4..offset * 4]);
+        out.extend_from_slice(&header.to_le_bytes());
+        out.extend_from_slice(&bytes[(offset + 1) * 4..end * 4]);
+        out.extend_from_slice(&[0; 4]);
+        copied = end;
+    }
+    out.extend_from_slice(&bytes[copied * 4..]);
+    Some(out)
+}
+
+/// Turns every `OpTerminateInvocation` into an `OpKill` rspirv can parse and
+/// returns the indices of those instructions, `ModuleLoader` marks them so
+/// they can be told apart from a real `OpKill` (see `is_terminate_invocation`).
+///
+/// Returns `None` when the binary doesn't use `OpTerminateInvocation`.
+pub(crate) fn replace_terminate_invocations(bytes: &[u8]) -> Option<(Vec<u8>, HashSet<usize>)> {
+    let replaced: Vec<(usize, usize)> = instructions(bytes)
+        .enumerate()
+        .filter(|&(_, (offset, word_count))| {
+            word_count == 1 && read_word(bytes, offset) & 0xffff == u32::from(TERMINATE_INVOCATION)
+        })
+        .map(|(index, (offset, _))| (index, offset))
+        .collect();
+    if replaced.is_empty() {
+        return None;
+    }
+    let mut out = bytes.to_vec();
+    let kill = (1u32 << 16) | spirv::Op::Kill as u32;
+    for &(_, offset) in &replaced {
+        out[offset * 4..offset * 4 + 4].copy_from_slice(&kill.to_le_bytes());
+    }
+    Some((out, replaced.into_iter().map(|(index, _)| index).collect()))
+}
+
+/// Whether `inst` is an `OpTerminateInvocation`, which is loaded as an
+/// `OpKill` with the real opcode as its only operand.
+pub(crate) fn is_terminate_invocation(inst: &Instruction) -> bool {
+    inst.class.opcode == spirv::Op::Kill
+        && inst.operands == [Operand::LiteralInt32(u32::from(TERMINATE_INVOCATION))]
+}
+
+/// Whether `opcode` ends a block.
+pub(crate) fn is_terminator(opcode: spirv::Op) -> bool {
+    match opcode {
+        // The KHR ray tracing terminators share their opcodes with the NV ones
+        spirv::Op::TerminateRayNV | spirv::Op::IgnoreIntersectionNV => true,
+        opcode => reflect::is_terminator(opcode),
+    }
+}
+
+/// Returns `$error` from the consumer if `$condition` holds.
+macro_rules! fail_if {
+    ($condition:expr, $error:expr) => {
+        if $condition {
+            return ParseAction::Error(Box::new($error));
+        }
+    };
+}
+
+/// A parser consumer that builds a `Module` and remembers how far it got.
+pub(crate) struct ModuleLoader {
+    module: Module,
+    function: Option<Function>,
+    block: Option<Block>,
+    consumed: usize,
+    /// The indices of the instructions that were `OpTerminateInvocation`s
+    terminate_invocations: HashSet<usize>,
+    /// Bit width of every `OpTypeInt`
+    int_widths: HashMap<Word, u32>,
+    /// Result type of every instruction that has one
+    value_types: HashMap<Word, Word>,
+    /// The `OpLine` in effect
+    line: Option<SourceLocation>,
+    /// The location of every instruction of the current block
+    block_locations: Vec<Option<SourceLocation>>,
+    /// The locations of every finished block, by block id
+    locations: HashMap<Word, Vec<Option<SourceLocation>>>,
+}
+
+impl ModuleLoader {
+    /// `terminate_invocations` are the instruction indices returned by
+    /// `replace_terminate_invocations`.
+    pub fn new(terminate_invocations: HashSet<usize>) -> Self {
+        ModuleLoader {
+            module: Module::new(),
+            function: None,
+            block: None,
+            consumed: 0,
+            terminate_invocations,
+            int_widths: HashMap::new(),
+            value_types: HashMap::new(),
+            line: None,
+            block_locations: Vec::new(),
+            locations: HashMap::new(),
+        }
+    }
+
+    /// The number of instructions handed to the loader so far.
+    pub fn consumed(&self) -> usize {
+        self.consumed
+    }
+
+    /// The module and the source location of every block instruction.
+    pub fn into_parts(self) -> (Module, HashMap<Word, Vec<Option<SourceLocation>>>) {
+        (self.module, self.locations)
+    }
+
+    fn track_types(&mut self, inst: &Instruction) {
+        if let (Some(id), Some(ty)) = (inst.result_id, inst.result_type) {
+            self.value_types.insert(id, ty);
+        }
+        if inst.class.opcode == spirv::Op::TypeInt {
+            if let (Some(id), Some(&Operand::LiteralInt32(width))) =
+                (inst.result_id, inst.operands.first())
+            {
+                self.int_widths.insert(id, width);
+            }
+        }
+    }
+
+    /// rspirv always decodes `OpSwitch` cases as a 32 bit literal followed by
+    /// the target, so a 64 bit selector ends up with its case words spread over
+    /// the wrong operands. The words are all there, this regroups them.
+    fn fix_wide_switch(&self, inst: &mut Instruction) {
+        let selector = match inst.operands.first() {
+            Some(&Operand::IdRef(selector)) => selector,
+            _ => return,
+        };
+        let width = self
+            .value_types
+            .get(&selector)
+            .and_then(|ty| self.int_widths.get(ty));
+        if width != Some(&64) || inst.operands.len() < 2 {
+            return;
+        }
+        let words: Option<Vec<Word>> = inst.operands[2..]
+            .iter()
+            .map(|operand| match *operand {
+                Operand::LiteralInt32(word) | Operand::IdRef(word) => Some(word),
+                _ => None,
+            })
+            .collect();
+        let words = match words {
+            Some(ref words) if words.len() % 3 == 0 => &words[..],
+            // Padded by `pad_wide_switches`
+            Some(ref words) if words.len() % 3 == 1 => &words[..words.len() - 1],
+            _ => return,
+        };
+        let cases = words.chunks(3).flat_map(|case| {
+            let value = u64::from(case[0]) | u64::from(case[1]) << 32;
+            vec![Operand::LiteralInt64(value), Operand::IdRef(case[2])]
+        });
+        let mut operands = inst.operands[..2].to_vec();
+        operands.extend(cases);
+        inst.operands = operands;
+    }
+}
+
+impl Consumer for ModuleLoader {
+    fn initialize(&mut self) -> ParseAction {
+        ParseAction::Continue
+    }
+
+    fn finalize(&mut self) -> ParseAction {
+        fail_if!(self.block.is_some(), Error::UnclosedBlock);
+        fail_if!(self.function.is_some(), Error::UnclosedFunction);
+        ParseAction::Continue
+    }
+
+    fn consume_header(&mut self, header: ModuleHeader) -> ParseAction {
+        self.module.header = Some(header);
+        ParseAction::Continue
+    }
+
+    fn consume_instruction(&mut self, mut inst: Instruction) -> ParseAction {
+        if self.terminate_invocations.contains(&self.consumed) {
+            inst.operands
+                .push(Operand::LiteralInt32(u32::from(TERMINATE_INVOCATION)));
+        }
+        self.consumed += 1;
+        self.track_types(&inst);
+        let opcode = inst.class.opcode;
+        match opcode {
+            spirv::Op::Capability => self.module.capabilities.push(inst),
+            spirv::Op::Extension => self.module.extensions.push(inst),
+            spirv::Op::ExtInstImport => self.module.ext_inst_imports.push(inst),
+            spirv::Op::MemoryModel => self.module.memory_model = Some(inst),
+            spirv::Op::EntryPoint => self.module.entry_points.push(inst),
+            spirv::Op::ExecutionMode => self.module.execution_modes.push(inst),
+            opcode if reflect::is_nonlocation_debug(opcode) => self.module.debugs.push(inst),
+            opcode if reflect::is_annotation(opcode) => self.module.annotations.push(inst),
+            opcode if reflect::is_type(opcode) || reflect::is_constant(opcode) => {
+                self.module.types_global_values.push(inst)
+            }
+            // Non-semantic instructions such as debug info can be global
+            spirv::Op::Variable | spirv::Op::Undef | spirv::Op::ExtInst
+                if self.function.is_none() =>
+            {
+                self.module.types_global_values.push(inst)
+            }
+            spirv::Op::Function => {
+                fail_if!(self.function.is_some(), Error::NestedFunction);
+                let mut function = Function::new();
+                function.def = Some(inst);
+                self.function = Some(function);
+                self.line = None;
+            }
+            spirv::Op::FunctionEnd => {
+                fail_if!(self.block.is_some(), Error::UnclosedBlock);
+                let mut function = match self.function.take() {
+                    Some(function) => function,
+                    None => return ParseAction::Error(Box::new(Error::MismatchedFunctionEnd)),
+                };
+                function.end = Some(inst);
+                self.module.functions.push(function);
+                self.line = None;
+            }
+            spirv::Op::FunctionParameter => match self.function {
+                Some(ref mut function) => function.parameters.push(inst),
+                None => {
+                    return ParseAction::Error(Box::new(Error::DetachedFunctionParameter));
+                }
+            },
+            spirv::Op::Label => {
+                fail_if!(self.function.is_none(), Error::DetachedBlock);
+                fail_if!(self.block.is_some(), Error::NestedBlock);
+                let mut block = Block::new();
+                block.label = Some(inst);
+                self.block = Some(block);
+                self.block_locations.clear();
+                // Nor does an `OpLine` in front of the block reach into it
+                self.line = None;
+            }
+            opcode if is_terminator(opcode) => {
+                if opcode == spirv::Op::Switch {
+                    self.fix_wide_switch(&mut inst);
+                }
+                let mut block = match self.block.take() {
+                    Some(block) => block,
+                    None => return ParseAction::Error(Box::new(Error::MismatchedTerminator)),
+                };
+                block.instructions.push(inst);
+                self.block_locations.push(self.line);
+                if let Some(id) = block.label.as_ref().and_then(|label| label.result_id) {
+                    self.locations
+                        .insert(id, std::mem::take(&mut self.block_locations));
+                }
+                // A block only exists inside of a function, see `Op::Label`.
+                self.function.as_mut().expect("function").blocks.push(block);
+                // The scope of an `OpLine` ends with the block
+                self.line = None;
+            }
+            spirv::Op::Line => {
+                self.line = match inst.operands.as_slice() {
+                    [Operand::IdRef(file), Operand::LiteralInt32(line), Operand::LiteralInt32(column)] => {
+                        Some(SourceLocation {
+                            file: *file,
+                            line: *line,
+                            column: *column,
+                        })
+                    }
+                    _ => None,
+                }
+            }
+            spirv::Op::NoLine => self.line = None,
+            spirv::Op::ModuleProcessed => {}
+            _ => match self.block {
+                Some(ref mut block) => {
+                    block.instructions.push(inst);
+                    self.block_locations.push(self.line);
+                }
+                None => {
+                    return ParseAction::Error(Box::new(Error::DetachedInstruction(Some(inst))));
+                }
+            },
+        }
+        ParseAction::Continue
+    }
+}
@@ -0,0 +1,191 @@
+//! Natural loop detection.
+//!
+//! The depth first search of the traversal module finds the back edges of the
+//! CFG, the edges that jump to a block that is still on the search stack. A
+//! back edge whose target dominates its source forms a natural loop, all other
+//! back edges make the CFG irreducible. Back edges with the same target share
+//! one loop.
+use super::{Merge, PetSpirv, Terminator};
+use spirv::Word;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Loop {
+    pub header: Word,
+    /// The blocks with a back edge to the header
+    pub latches: Vec<Word>,
+    /// Every block of the loop, including the header and nested loops
+    pub body: BTreeSet<Word>,
+    /// Blocks outside of the loop that are targets of edges leaving it
+    pub exits: BTreeSet<Word>,
+    /// 1 for outermost loops
+    pub depth: usize,
+    /// Index of the enclosing loop in `LoopInfo::loops`
+    pub parent: Option<usize>,
+    /// Indices of the loops directly nested in this one
+    pub children: Vec<usize>,
+    /// The merge instruction declared by the header, if any
+    pub merge: Option<Merge>,
+}
+
+/// A disagreement between the natural loops and the declared `OpLoopMerge`s.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoopMismatch {
+    /// The header of a natural loop has no `OpLoopMerge`.
+    MissingLoopMerge { header: Word },
+    /// A block declares `OpLoopMerge` but no back edge targets it.
+    NotALoop { header: Word },
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct LoopInfo {
+    /// Outer loops come before the loops nested in them
+    pub loops: Vec<Loop>,
+    /// Back edges `(source, target)` whose target doesn't dominate the source
+    pub irreducible_edges: Vec<(Word, Word)>,
+    /// Blocks declaring `OpLoopMerge`
+    declared_headers: BTreeSet<Word>,
+    innermost: HashMap<Word, usize>,
+}
+
+impl LoopInfo {
+    pub fn new(pet: &PetSpirv) -> Self {
+        let dominators = pet.dominator_tree();
+        let mut latches: BTreeMap<Word, Vec<Word>> = BTreeMap::new();
+        let mut irreducible_edges = Vec::new();
+        let mut header_order = Vec::new();
+        for (source, target) in pet.back_edges() {
+            if dominators.dominates(target, source) {
+                let latches = latches.entry(target).or_default();
+                if latches.is_empty() {
+                    header_order.push(target);
+                }
+                latches.push(source);
+            } else {
+                irreducible_edges.push((source, target));
+            }
+        }
+
+        let mut predecessors: HashMap<Word, Vec<Word>> = HashMap::new();
+        for &id in pet.block_map.keys() {
+            for succ in pet.successors(id) {
+                predecessors.entry(succ).or_default().push(id);
+            }
+        }
+
+        let mut loops: Vec<Loop> = header_order
+            .into_iter()
+            .map(|header| {
+                let latches = latches.remove(&header).expect("latches");
+                let body = loop_body(header, &latches, &predecessors, |id| {
+                    dominators.dominates(header, id)
+                });
+                let exits = body
+                    .iter()
+                    .flat_map(|&id| pet.successors(id))
+                    .filter(|succ| !body.contains(succ))
+                    .collect();
+                Loop {
+                    header,
+                    latches,
+                    body,
+                    exits,
+                    depth: 1,
+                    parent: None,
+                    children: Vec::new(),
+                    merge: Terminator::from_basic_block(pet.get_block(header)).merge(),
+                }
+            })
+            .collect();
+        // Bigger loops first, so a loop's parent always has a smaller index
+        loops.sort_by_key(|l| Reverse(l.body.len()));
+
+        let mut innermost = HashMap::new();
+        for index in 0..loops.len() {
+            let parent = innermost.get(&loops[index].header).cloned();
+            if let Some(parent) = parent {
+                loops[index].parent = Some(parent);
+                loops[index].depth = loops[parent].depth + 1;
+                loops[parent].children.push(index);
+            }
+            for &id in &loops[index].body {
+                innermost.insert(id, index);
+            }
+        }
+
+        let declared_headers = pet
+            .block_map
+            .iter()
+            .filter(|&(_, block)| {
+                matches!(
+                    Terminator::from_basic_block(block).merge(),
+                    Some(Merge::Loop { .. })
+                )
+            })
+            .map(|(&id, _)| id)
+            .collect();
+        LoopInfo {
+            loops,
+            irreducible_edges,
+            declared_headers,
+            innermost,
+        }
+    }
+
+    /// The index of the innermost loop containing `block`.
+    pub fn innermost_loop(&self, block: Word) -> Option<usize> {
+        self.innermost.get(&block).cloned()
+    }
+
+    /// The number of loops containing `block`, 0 outside of any loop.
+    pub fn loop_depth(&self, block: Word) -> usize {
+        self.innermost_loop(block)
+            .map_or(0, |index| self.loops[index].depth)
+    }
+
+    /// Indices of the loops that are not nested in another loop.
+    pub fn top_level(&self) -> Vec<usize> {
+        (0..self.loops.len())
+            .filter(|&index| self.loops[index].parent.is_none())
+            .collect()
+    }
+
+    /// Compares the natural loops with the `OpLoopMerge` declarations.
+    pub fn mismatches(&self) -> Vec<LoopMismatch> {
+        let headers: BTreeSet<Word> = self.loops.iter().map(|l| l.header).collect();
+        let missing = headers
+            .difference(&self.declared_headers)
+            .map(|&header| LoopMismatch::MissingLoopMerge { header });
+        let not_a_loop = self
+            .declared_headers
+            .difference(&headers)
+            .map(|&header| LoopMismatch::NotALoop { header });
+        missing.chain(not_a_loop).collect()
+    }
+}
+
+/// The header plus every block dominated by it that reaches a latch without
+/// passing the header.
+fn loop_body(
+    header: Word,
+    latches: &[Word],
+    predecessors: &HashMap<Word, Vec<Word>>,
+    dominated: impl Fn(Word) -> bool,
+) -> BTreeSet<Word> {
+    let mut body = BTreeSet::new();
+    body.insert(header);
+    let mut worklist: Vec<Word> = latches.to_vec();
+    while let Some(id) = worklist.pop() {
+        if dominated(id) && body.insert(id) {
+            worklist.extend(predecessors.get(&id).into_iter().flatten().cloned());
+        }
+    }
+    body
+}
+
+impl<'spir> PetSpirv<'spir> {
+    pub fn loop_info(&self) -> LoopInfo {
+        LoopInfo::new(self)
+    }
+}
@@ -1,8 +1,17 @@
 extern crate clap;
+extern crate regex;
 extern crate rspirv_cfg;
-use clap::{App, Arg};
-use rspirv_cfg::{export_spirv_cfg, SpirvModule};
-use std::path::PathBuf;
+use clap::{App, Arg, ArgMatches};
+use regex::Regex;
+use rspirv_cfg::{
+    export_spirv_cfg_per_function, write_call_graph, write_dominator_trees, write_html, write_json,
+    write_mermaid, write_spirv_cfg, write_svg, write_text, BlockOrder, CallGraph, CfgError,
+    DominatorTreeKind, DotOptions, FunctionFilter, LoopMismatch, MermaidOptions, PetSpirv,
+    SourceView, SpirvModule, TextOptions,
+};
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::process;
 fn main() {
     let matches = App::new("rspirv-cfg")
         .arg(
@@ -10,14 +19,434 @@ fn main() {
                 .short("f")
                 .long("file")
                 .value_name("FILE")
-                .help("Path to the .spv file")
+                .help("Path to the .spv file, or - to read it from stdin")
                 .required(true)
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .value_name("FILE")
+                .help("Where to write the graph, - writes it to stdout \
+                     [default: test.<format>, stdout for text]")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .value_name("FORMAT")
+                .help("The output format")
+                .possible_values(&["dot", "svg", "json", "mermaid", "html", "text"])
+                .default_value("dot")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("split")
+                .long("split")
+                .help("Write one <function>.dot file per function into the --output directory")
+                .conflicts_with("call-graph"),
+        )
+        .arg(
+            Arg::with_name("color-branches")
+                .long("color-branches")
+                .help("Draw true edges of conditional branches green and false edges red"),
+        )
+        .arg(
+            Arg::with_name("dominators")
+                .long("dominators")
+                .value_name("MODE")
+                .help(
+                    "Draw the dominator tree (tree), the post-dominator tree (post-tree) \
+                     or the CFG with immediate dominator edges (overlay)",
+                )
+                .possible_values(&["tree", "post-tree", "overlay"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("loops")
+                .long("loops")
+                .help("Draw natural loops as nested clusters and report loops that disagree with their OpLoopMerge"),
+        )
+        .arg(
+            Arg::with_name("order")
+                .long("order")
+                .value_name("ORDER")
+                .help(
+                    "Write blocks in module layout order (layout), reverse postorder (rpo) \
+                     or by id (id)",
+                )
+                .possible_values(&["layout", "rpo", "id"])
+                .default_value("layout")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("call-graph")
+                .long("call-graph")
+                .help("Draw the call graph of the module instead of the CFG")
+                .conflicts_with("dominators"),
+        )
+        .arg(
+            Arg::with_name("call-edges")
+                .long("call-edges")
+                .help("Link blocks with an OpFunctionCall to the cluster of the called function"),
+        )
+        .arg(
+            Arg::with_name("function")
+                .long("function")
+                .value_name("GLOB")
+                .help("Only draw functions whose name matches the glob pattern")
+                .multiple(true)
+                .number_of_values(1)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("function-regex")
+                .long("function-regex")
+                .value_name("REGEX")
+                .help("Only draw functions whose name matches the regular expression")
+                .multiple(true)
+                .number_of_values(1)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("function-id")
+                .long("function-id")
+                .value_name("ID")
+                .help("Only draw the function with this result id, with or without a leading %")
+                .multiple(true)
+                .number_of_values(1)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("entry-point")
+                .long("entry-point")
+                .value_name("NAME")
+                .help("Only draw the entry point and the functions it calls")
+                .multiple(true)
+                .number_of_values(1)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("max-instructions")
+                .long("max-instructions")
+                .value_name("N")
+                .help("Show at most N instructions per block (mermaid, text)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("max-line-width")
+                .long("max-line-width")
+                .value_name("N")
+                .help("Cut instructions after N characters (mermaid, text)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("source")
+                .long("source")
+                .value_name("MODE")
+                .help(
+                    "Show the source lines from OpLine or DebugLine before the instructions \
+                     compiled from them (interleave) or instead of them (replace) \
+                     (dot, svg, text)",
+                )
+                .possible_values(&["interleave", "replace"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("hide-debug-info")
+                .long("hide-debug-info")
+                .help(
+                    "Leave NonSemantic.Shader.DebugInfo.100 instructions out of the blocks, \
+                     --source still uses them (dot, svg, text)",
+                ),
+        )
+        .arg(
+            Arg::with_name("inline-types")
+                .long("inline-types")
+                .help("Show types structurally, e.g. vec4<f32>, instead of their ids"),
+        )
+        .arg(
+            Arg::with_name("inline-constants")
+                .long("inline-constants")
+                .help("Show the values of constants, e.g. 1.0f, instead of their ids"),
+        )
+        .arg(
+            Arg::with_name("ascii")
+                .long("ascii")
+                .help("Draw the text format with ASCII characters only"),
+        )
+        .arg(
+            Arg::with_name("hide-unreachable")
+                .long("hide-unreachable")
+                .help("Leave out blocks that can't be reached from the entry block"),
+        )
+        .arg(
+            Arg::with_name("validate")
+                .long("validate")
+                .help("Check the structured control flow rules, report violations and draw them red"),
+        )
         .get_matches();
     let file_path = matches.value_of("file").expect("No filename");
-    let file_path = PathBuf::from(file_path);
-    let module = SpirvModule::load(&file_path);
-    export_spirv_cfg(&module);
+    let loaded = if file_path == "-" {
+        let mut bytes = Vec::new();
+        io::stdin()
+            .read_to_end(&mut bytes)
+            .map_err(CfgError::from)
+            .and_then(|_| SpirvModule::from_bytes(&bytes))
+    } else {
+        SpirvModule::try_load(&file_path)
+    };
+    let mut module = match loaded {
+        Ok(module) => module,
+        Err(err) => {
+            let source = if file_path == "-" {
+                "<stdin>"
+            } else {
+                file_path
+            };
+            eprintln!("error: {}: {}", source, err);
+            process::exit(1);
+        }
+    };
+    if matches.is_present("inline-types") {
+        module.inline_types();
+    }
+    if matches.is_present("inline-constants") {
+        module.inline_constants();
+    }
+    let options = DotOptions {
+        color_branches: matches.is_present("color-branches"),
+        idom_edges: matches.value_of("dominators") == Some("overlay"),
+        loop_clusters: matches.is_present("loops"),
+        highlight_violations: matches.is_present("validate"),
+        hide_unreachable: matches.is_present("hide-unreachable"),
+        block_order: match matches.value_of("order") {
+            Some("rpo") => BlockOrder::ReversePostorder,
+            Some("id") => BlockOrder::Id,
+            _ => BlockOrder::Layout,
+        },
+        call_edges: matches.is_present("call-edges"),
+        source_view: match matches.value_of("source") {
+            Some("interleave") => SourceView::Interleave,
+            Some("replace") => SourceView::Replace,
+            _ => SourceView::Disassembly,
+        },
+        hide_debug_info: matches.is_present("hide-debug-info"),
+        functions: function_filter(&matches),
+    };
+    let format = matches.value_of("format").expect("No format");
+    if format != "dot" {
+        let dot_only = ["dominators", "loops", "call-graph", "call-edges"];
+        if let Some(flag) = dot_only.iter().find(|&&flag| matches.is_present(flag)) {
+            eprintln!("error: --{} only works with the dot format", flag);
+            process::exit(1);
+        }
+    }
+    let call_graph = CallGraph::new(&module);
+    if options
+        .functions
+        .select_ids(&module, &call_graph)
+        .is_empty()
+    {
+        eprintln!("warning: no function matches the filter");
+    }
+    if options.loop_clusters {
+        report_loop_mismatches(&module, &options.functions, &call_graph);
+    }
+    let valid = !options.highlight_violations
+        || report_violations(&module, &options.functions, &call_graph);
+    report_recursion(&module, &call_graph);
+    let default_output = match format {
+        "text" => "-".to_string(),
+        format => format!("test.{}", format),
+    };
+    let output = matches.value_of("output").unwrap_or(&default_output);
+    let written = if matches.is_present("split") {
+        if format != "dot" {
+            eprintln!("error: --split only supports the dot format");
+            process::exit(1);
+        }
+        if let Some(mode @ "tree") | Some(mode @ "post-tree") = matches.value_of("dominators") {
+            eprintln!("error: --split can't draw --dominators {}", mode);
+            process::exit(1);
+        }
+        if output == "-" {
+            eprintln!("error: --split writes files into the --output directory, not to stdout");
+            process::exit(1);
+        }
+        let dir = matches.value_of("output").unwrap_or(".");
+        export_spirv_cfg_per_function(&module, &call_graph, &options, dir).map(|_| ())
+    } else if output == "-" {
+        let stdout = io::stdout();
+        let mut lock = stdout.lock();
+        render(&module, &call_graph, &matches, &options, &mut lock).and_then(|_| lock.flush())
+    } else {
+        File::create(output).and_then(|file| {
+            let mut file = BufWriter::new(file);
+            render(&module, &call_graph, &matches, &options, &mut file)?;
+            file.flush()
+        })
+    };
+    if let Err(err) = written {
+        eprintln!("error: {}: {}", output, err);
+        process::exit(1);
+    }
+    if !valid {
+        process::exit(1);
+    }
     //println!("{:#?}", module.names);
 }
+
+fn function_filter(matches: &ArgMatches) -> FunctionFilter {
+    let values = |name| -> Vec<String> {
+        matches
+            .values_of(name)
+            .map(|values| values.map(String::from).collect())
+            .unwrap_or_default()
+    };
+    let name_regexes = values("function-regex")
+        .iter()
+        .map(|pattern| {
+            Regex::new(pattern).unwrap_or_else(|err| {
+                eprintln!("error: --function-regex: {}", err);
+                process::exit(1);
+            })
+        })
+        .collect();
+    let ids = values("function-id")
+        .iter()
+        .map(|id| {
+            id.trim_start_matches('%').parse().unwrap_or_else(|_| {
+                eprintln!("error: --function-id: {} is not a result id", id);
+                process::exit(1);
+            })
+        })
+        .collect();
+    FunctionFilter {
+        names: values("function"),
+        name_regexes,
+        ids,
+        entry_points: values("entry-point"),
+    }
+}
+
+/// Prints every structured control flow violation, returns whether there were none.
+fn report_violations(module: &SpirvModule, filter: &FunctionFilter, graph: &CallGraph) -> bool {
+    let mut valid = true;
+    for f in filter.select(module, graph) {
+        let fn_name = module.get_name_fn(f).unwrap_or("Unknown");
+        for violation in PetSpirv::new(module, f).validate() {
+            eprintln!("error: {}: {}", fn_name, violation.message(module));
+            valid = false;
+        }
+    }
+    valid
+}
+
+fn report_loop_mismatches(module: &SpirvModule, filter: &FunctionFilter, graph: &CallGraph) {
+    for f in filter.select(module, graph) {
+        let pet = PetSpirv::new(module, f);
+        let fn_name = module.get_name_fn(f).unwrap_or("Unknown");
+        let loops = pet.loop_info();
+        for mismatch in loops.mismatches() {
+            match mismatch {
+                LoopMismatch::MissingLoopMerge { header } => eprintln!(
+                    "warning: {}: loop header {} has no OpLoopMerge",
+                    fn_name,
+                    module.name_or_id(Some(header)).expect("name")
+                ),
+                LoopMismatch::NotALoop { header } => eprintln!(
+                    "warning: {}: {} declares OpLoopMerge but is not a loop header",
+                    fn_name,
+                    module.name_or_id(Some(header)).expect("name")
+                ),
+            }
+        }
+        for (source, target) in loops.irreducible_edges {
+            eprintln!(
+                "warning: {}: irreducible back edge {} -> {}",
+                fn_name,
+                module.name_or_id(Some(source)).expect("name"),
+                module.name_or_id(Some(target)).expect("name")
+            );
+        }
+    }
+}
+
+/// Recursion is not allowed in shaders, so it's worth a warning.
+fn report_recursion(module: &SpirvModule, graph: &CallGraph) {
+    for group in graph.recursion() {
+        let names: Vec<String> = group
+            .iter()
+            .map(|&function| module.name_or_id(Some(function)).expect("name"))
+            .collect();
+        eprintln!("warning: recursive functions: {}", names.join(", "));
+    }
+}
+
+fn number_arg(matches: &ArgMatches, name: &str) -> Option<usize> {
+    matches.value_of(name).map(|value| {
+        value.parse().unwrap_or_else(|_| {
+            eprintln!("error: --{}: {} is not a number", name, value);
+            process::exit(1);
+        })
+    })
+}
+
+fn render(
+    module: &SpirvModule,
+    graph: &CallGraph,
+    matches: &ArgMatches,
+    options: &DotOptions,
+    mut write: &mut dyn Write,
+) -> io::Result<()> {
+    match matches.value_of("format") {
+        Some("json") => return write_json(module, graph, &options.functions, &mut write),
+        Some("html") => {
+            let title = matches.value_of("file").expect("No filename");
+            return write_html(module, graph, &options.functions, title, &mut write);
+        }
+        Some("mermaid") => {
+            let options = MermaidOptions {
+                max_instructions: number_arg(matches, "max-instructions"),
+                max_line_width: number_arg(matches, "max-line-width"),
+                functions: options.functions.clone(),
+            };
+            return write_mermaid(module, graph, &options, &mut write);
+        }
+        Some("svg") => return write_svg(module, graph, options, &mut write),
+        Some("text") => {
+            let options = TextOptions {
+                ascii: matches.is_present("ascii"),
+                max_instructions: number_arg(matches, "max-instructions"),
+                max_line_width: number_arg(matches, "max-line-width"),
+                source_view: options.source_view,
+                hide_debug_info: options.hide_debug_info,
+                functions: options.functions.clone(),
+            };
+            return write_text(module, graph, &options, &mut write);
+        }
+        _ => {}
+    }
+    if matches.is_present("call-graph") {
+        return write_call_graph(module, graph, options, &mut write);
+    }
+    match matches.value_of("dominators") {
+        Some("tree") => write_dominator_trees(
+            module,
+            graph,
+            options,
+            DominatorTreeKind::Dominator,
+            &mut write,
+        ),
+        Some("post-tree") => write_dominator_trees(
+            module,
+            graph,
+            options,
+            DominatorTreeKind::PostDominator,
+            &mut write,
+        ),
+        _ => write_spirv_cfg(module, graph, options, &mut write),
+    }
+}
@@ -0,0 +1,181 @@
+//! Mermaid output.
+//!
+//! Every function becomes a subgraph of one `flowchart TD`. Blocks are named
+//! `b<id>`, the function node in front of the entry block `fn<id>`.
+use super::dot::header_color;
+use super::{
+    disassemble_text, CallGraph, EdgeKind, FunctionFilter, PetSpirv, SpirvModule, Terminator,
+};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Controls what ends up in the Mermaid output.
+#[derive(Clone, Debug, Default)]
+pub struct MermaidOptions {
+    /// Show at most this many instructions per block.
+    pub max_instructions: Option<usize>,
+    /// Cut instructions longer than this many characters.
+    pub max_line_width: Option<usize>,
+    /// The functions to draw, all of them if the filter is empty.
+    pub functions: FunctionFilter,
+}
+
+/// Writes the control flow graph of every function in the module as a Mermaid
+/// flowchart to `path`.
+pub fn export_mermaid<P: AsRef<Path>>(
+    module: &SpirvModule,
+    options: &MermaidOptions,
+    path: P,
+) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    write_mermaid(module, &CallGraph::new(module), options, &mut file)?;
+    file.flush()
+}
+
+/// Writes the control flow graph of every function in the module as a Mermaid
+/// flowchart into `write`.
+pub fn write_mermaid(
+    module: &SpirvModule,
+    graph: &CallGraph,
+    options: &MermaidOptions,
+    write: &mut impl Write,
+) -> io::Result<()> {
+    writeln!(write, "flowchart TD")?;
+    // Links are styled by their index in the whole chart
+    let mut links = 0;
+    for f in options.functions.select(module, graph) {
+        PetSpirv::new(module, f).add_fn_to_mermaid(options, &mut links, write)?;
+    }
+    Ok(())
+}
+
+/// Escapes text for a quoted Mermaid label. Mermaid decodes `#<code>;` entity
+/// codes, so everything that could end the label or be read as markup is
+/// replaced by one.
+pub fn escape_mermaid(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' | '#' | '&' | '<' | '>' | '|' | '`' => escaped.push_str(&format!("#{};", c as u32)),
+            '\n' => escaped.push_str("<br/>"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Cuts `line` to at most `max_width` characters, the last one of which
+/// becomes `ellipsis`.
+pub(crate) fn truncate(line: String, max_width: Option<usize>, ellipsis: char) -> String {
+    match max_width {
+        Some(width) if line.chars().count() > width => {
+            let mut line: String = line.chars().take(width.saturating_sub(1)).collect();
+            line.push(ellipsis);
+            line
+        }
+        _ => line,
+    }
+}
+
+impl<'spir> PetSpirv<'spir> {
+    /// Writes this function as a subgraph. `links` counts the links written so
+    /// far in the whole flowchart.
+    pub fn add_fn_to_mermaid(
+        &self,
+        options: &MermaidOptions,
+        links: &mut usize,
+        write: &mut impl Write,
+    ) -> io::Result<()> {
+        let fn_name = self.module.get_name_fn(self.function).unwrap_or("Unknown");
+        let fn_id = self.function_id();
+        let reachable = self.reachable_blocks();
+        writeln!(
+            write,
+            "  subgraph f{} [\"{}\"]",
+            fn_id,
+            escape_mermaid(fn_name)
+        )?;
+        writeln!(write, "    fn{}([\"{}\"])", fn_id, escape_mermaid(fn_name))?;
+        for block in &self.function.blocks {
+            let id = match block.label.as_ref().and_then(|label| label.result_id) {
+                Some(id) => id,
+                None => continue,
+            };
+            let name = self.module.name_or_id(Some(id)).expect("name");
+            let mut lines = vec![format!("<b>{}</b>", escape_mermaid(&name))];
+            let shown = options
+                .max_instructions
+                .unwrap_or(block.instructions.len())
+                .min(block.instructions.len());
+            for inst in &block.instructions[..shown] {
+                let text = truncate(
+                    disassemble_text(self.module, inst),
+                    options.max_line_width,
+                    '…',
+                );
+                lines.push(escape_mermaid(&text));
+            }
+            if shown < block.instructions.len() {
+                lines.push(format!("… {} more", block.instructions.len() - shown));
+            }
+            writeln!(write, "    b{}[\"{}\"]", id, lines.join("<br/>"))?;
+            let terminator = Terminator::from_basic_block(block);
+            if !reachable.contains(&id) {
+                writeln!(
+                    write,
+                    "    style b{} fill:whitesmoke,stroke:gray,stroke-dasharray:5 5,color:gray",
+                    id
+                )?;
+            } else {
+                match header_color(&terminator) {
+                    "gray" => {}
+                    color => writeln!(write, "    style b{} fill:{}", id, color)?,
+                }
+            }
+        }
+        writeln!(write, "  end")?;
+
+        if let Some(entry) = self.entry_block() {
+            writeln!(write, "  fn{} --> b{}", fn_id, entry)?;
+            *links += 1;
+        }
+        for block in &self.function.blocks {
+            let id = match block.label.as_ref().and_then(|label| label.result_id) {
+                Some(id) => id,
+                None => continue,
+            };
+            let terminator = Terminator::from_basic_block(block);
+            for edge in terminator.edges() {
+                let label = self.edge_label(&terminator, &edge);
+                let arrow = match edge.kind {
+                    EdgeKind::SelectionMerge | EdgeKind::LoopMerge | EdgeKind::Continue => "-.->",
+                    EdgeKind::Case { default: true, .. } => "==>",
+                    _ if !reachable.contains(&id) => "-.->",
+                    _ => "-->",
+                };
+                match label {
+                    Some(label) => writeln!(
+                        write,
+                        "  b{} {}|\"{}\"| b{}",
+                        id,
+                        arrow,
+                        escape_mermaid(&label),
+                        edge.target
+                    )?,
+                    None => writeln!(write, "  b{} {} b{}", id, arrow, edge.target)?,
+                }
+                let color = match edge.kind {
+                    _ if !reachable.contains(&id) => Some("gray"),
+                    EdgeKind::LoopMerge | EdgeKind::Continue => Some("blue"),
+                    _ => None,
+                };
+                if let Some(color) = color {
+                    writeln!(write, "  linkStyle {} stroke:{}", links, color)?;
+                }
+                *links += 1;
+            }
+        }
+        Ok(())
+    }
+}
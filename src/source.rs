@@ -0,0 +1,246 @@
+//! The source map built from `OpString`, `OpSource`, `OpSourceContinued`,
+//! `OpLine` and `OpNoLine`.
+use super::{disassemble_text, CfgError, DebugInfo, PetSpirv};
+use rspirv::dr::{Instruction, Module, Operand};
+use spirv::Word;
+use std::collections::{BTreeMap, HashMap};
+
+/// Where an instruction came from, as declared by the `OpLine` in effect.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SourceLocation {
+    /// The `OpString` holding the file name
+    pub file: Word,
+    pub line: u32,
+    pub column: u32,
+}
+
+/// An `OpSource` together with the text of its `OpSourceContinued` instructions.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Source {
+    pub language: spirv::SourceLanguage,
+    pub version: u32,
+    /// The `OpString` holding the file name
+    pub file: Option<Word>,
+    /// The embedded source text
+    pub text: Option<String>,
+}
+
+/// The debug information relating instructions to the source they were
+/// compiled from.
+#[derive(Clone, Debug, Default)]
+pub struct SourceMap {
+    /// Every `OpString` by id
+    pub strings: BTreeMap<Word, String>,
+    pub sources: Vec<Source>,
+    /// The location of every instruction of a block, by block id
+    locations: HashMap<Word, Vec<Option<SourceLocation>>>,
+}
+
+/// What the blocks of a function show.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SourceView {
+    /// The disassembled instructions only
+    #[default]
+    Disassembly,
+    /// The source lines in front of the instructions compiled from them
+    Interleave,
+    /// The source lines instead of the instructions, blocks without line
+    /// information keep their instructions
+    Replace,
+}
+
+/// A line of a block listing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum ListingLine {
+    Instruction(String),
+    /// A source line labeled `file:line` or the lexical scope of the
+    /// instructions that follow
+    Source(String),
+}
+
+impl SourceMap {
+    /// Collects the strings and sources from the debug instructions of a module.
+    /// A malformed `OpSource` is skipped along with its `OpSourceContinued`s,
+    /// as is an `OpSourceContinued` that doesn't follow source text.
+    pub(crate) fn new(debugs: &[Instruction]) -> Result<Self, CfgError> {
+        let mut map = SourceMap::default();
+        // Whether an `OpSourceContinued` extends the text of the last source
+        let mut continues = false;
+        for inst in debugs {
+            match inst.class.opcode {
+                spirv::Op::String => match (inst.result_id, inst.operands.as_slice()) {
+                    (Some(id), [Operand::LiteralString(string)]) => {
+                        map.strings.insert(id, string.clone());
+                    }
+                    _ => return Err(CfgError::MalformedDebugInstruction(inst.clone())),
+                },
+                spirv::Op::Source => {
+                    continues = false;
+                    let (language, version, rest) = match inst.operands.as_slice() {
+                        [Operand::SourceLanguage(language), Operand::LiteralInt32(version), rest @ ..] => {
+                            (*language, *version, rest)
+                        }
+                        _ => continue,
+                    };
+                    let (file, text) = match rest {
+                        [] => (None, None),
+                        [Operand::IdRef(file)] => (Some(*file), None),
+                        [Operand::IdRef(file), Operand::LiteralString(text)] => {
+                            (Some(*file), Some(text.clone()))
+                        }
+                        _ => continue,
+                    };
+                    continues = text.is_some();
+                    map.sources.push(Source {
+                        language,
+                        version,
+                        file,
+                        text,
+                    });
+                }
+                spirv::Op::SourceContinued => {
+                    let text = map
+                        .sources
+                        .last_mut()
+                        .filter(|_| continues)
+                        .and_then(|source| source.text.as_mut());
+                    if let (Some(text), [Operand::LiteralString(continued)]) =
+                        (text, inst.operands.as_slice())
+                    {
+                        text.push_str(continued);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(map)
+    }
+
+    /// Sets the locations of the instructions of every block, as recorded by
+    /// the loader.
+    pub(crate) fn set_locations(&mut self, locations: HashMap<Word, Vec<Option<SourceLocation>>>) {
+        self.locations = locations;
+    }
+
+    /// Adds the sources and the `DebugLine` locations of the shader debug
+    /// info. `OpLine` takes precedence where both are given.
+    pub(crate) fn add_debug_info(&mut self, module: &Module, debug_info: &DebugInfo) {
+        if debug_info.is_empty() {
+            return;
+        }
+        for (file, text, language) in debug_info.sources(module) {
+            self.sources.push(Source {
+                language,
+                version: 0,
+                file: Some(file),
+                text,
+            });
+        }
+        for block in module.functions.iter().flat_map(|f| &f.blocks) {
+            let id = match block.label.as_ref().and_then(|label| label.result_id) {
+                Some(id) => id,
+                None => continue,
+            };
+            let lines = debug_info.block_locations(block);
+            if lines.iter().all(Option::is_none) {
+                continue;
+            }
+            let locations = self
+                .locations
+                .entry(id)
+                .or_insert_with(|| vec![None; lines.len()]);
+            for (location, line) in locations.iter_mut().zip(lines) {
+                if location.is_none() {
+                    *location = line;
+                }
+            }
+        }
+    }
+
+    /// Whether the module has no line information at all.
+    pub fn is_empty(&self) -> bool {
+        self.locations
+            .values()
+            .all(|locations| locations.iter().all(Option::is_none))
+    }
+
+    /// The location of instruction `index` of block `block`.
+    pub fn location(&self, block: Word, index: usize) -> Option<SourceLocation> {
+        *self.locations.get(&block)?.get(index)?
+    }
+
+    /// The name of a file, `%<id>` if the `OpString` is missing.
+    pub fn file_name(&self, file: Word) -> String {
+        self.strings
+            .get(&file)
+            .cloned()
+            .unwrap_or_else(|| format!("%{}", file))
+    }
+
+    /// The text of a line, counted from 1, if the file's source is embedded.
+    pub fn line_text(&self, file: Word, line: u32) -> Option<&str> {
+        let text = self
+            .sources
+            .iter()
+            .filter(|source| source.file == Some(file))
+            .find_map(|source| source.text.as_ref())?;
+        text.lines().nth((line as usize).checked_sub(1)?)
+    }
+
+    /// `file:line` followed by the text of the line if it's known.
+    pub fn describe(&self, location: SourceLocation) -> String {
+        let label = format!("{}:{}", self.file_name(location.file), location.line);
+        match self.line_text(location.file, location.line) {
+            Some(text) if !text.trim().is_empty() => format!("{}: {}", label, text.trim()),
+            _ => label,
+        }
+    }
+}
+
+impl<'spir> PetSpirv<'spir> {
+    /// The lines shown for a block: its instructions, the source lines they
+    /// were compiled from, or both. Source lines come with the lexical scope
+    /// from the shader debug info, and the debug info instructions themselves
+    /// can be left out.
+    pub(crate) fn block_listing(
+        &self,
+        id: Word,
+        view: SourceView,
+        hide_debug_info: bool,
+    ) -> Vec<ListingLine> {
+        let block = self.get_block(id);
+        let source_map = &self.module.source_map;
+        let debug_info = &self.module.debug_info;
+        let located =
+            (0..block.instructions.len()).any(|index| source_map.location(id, index).is_some());
+        let scopes = debug_info.block_scopes(block);
+        let mut listing = Vec::new();
+        let mut previous: Option<(Word, u32)> = None;
+        let mut previous_scope = None;
+        for (index, inst) in block.instructions.iter().enumerate() {
+            if view != SourceView::Disassembly {
+                if scopes[index].is_some() && scopes[index] != previous_scope {
+                    let name = debug_info.scope_name(scopes[index].expect("scope"));
+                    listing.push(ListingLine::Source(format!("in {}", name)));
+                    previous_scope = scopes[index];
+                }
+                if let Some(location) = source_map.location(id, index) {
+                    if previous != Some((location.file, location.line)) {
+                        listing.push(ListingLine::Source(source_map.describe(location)));
+                    }
+                    previous = Some((location.file, location.line));
+                }
+            }
+            if hide_debug_info && debug_info.is_debug_instruction(inst) {
+                continue;
+            }
+            if view != SourceView::Replace || !located {
+                listing.push(ListingLine::Instruction(disassemble_text(
+                    self.module,
+                    inst,
+                )));
+            }
+        }
+        listing
+    }
+}
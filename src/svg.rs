@@ -0,0 +1,413 @@
+//! SVG output laid out by the built-in layered layout, no graphviz needed.
+//!
+//! Blocks are drawn like the HTML tables of the DOT output: a header with the
+//! block name colored by how the block exits, followed by its instructions.
+//! Every function is framed like a DOT cluster, the frames are placed side by
+//! side.
+use super::dot::{header_color, NodeStyle};
+use super::layout::layout;
+use super::{
+    escape_html, CallGraph, DotOptions, EdgeKind, ListingLine, PetSpirv, SpirvModule, Terminator,
+};
+use spirv::Word;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+const FONT_SIZE: f64 = 12.0;
+/// The advance of a character of the monospace font at `FONT_SIZE`.
+const CHAR_WIDTH: f64 = 7.2;
+const LINE_HEIGHT: f64 = 15.0;
+const PADDING: f64 = 4.0;
+/// Space around the graph inside a function frame.
+const FRAME_MARGIN: f64 = 12.0;
+/// Space between two function frames.
+const FRAME_GAP: f64 = 24.0;
+
+/// The stroke colors edges can have, every one needs its own arrow head.
+const EDGE_COLORS: [&str; 6] = ["black", "blue", "darkgreen", "red3", "gray60", "red"];
+
+/// Writes the control flow graph of every selected function as SVG to `path`.
+pub fn export_svg<P: AsRef<Path>>(
+    module: &SpirvModule,
+    options: &DotOptions,
+    path: P,
+) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    write_svg(module, &CallGraph::new(module), options, &mut file)?;
+    file.flush()
+}
+
+/// Writes the control flow graph of every selected function as SVG into `write`.
+///
+/// Blocks, edges and their styles follow the DOT output for the same options,
+/// except for loop clusters, dominator edges and call edges, which are only
+/// drawn by graphviz.
+pub fn write_svg(
+    module: &SpirvModule,
+    graph: &CallGraph,
+    options: &DotOptions,
+    write: &mut impl Write,
+) -> io::Result<()> {
+    let mut body = Vec::new();
+    let mut width: f64 = 0.0;
+    let mut height: f64 = 0.0;
+    for f in options.functions.select(module, graph) {
+        if width > 0.0 {
+            width += FRAME_GAP;
+        }
+        writeln!(body, "<g transform=\"translate({:.1},0)\">", width)?;
+        let size = PetSpirv::new(module, f).add_fn_to_svg(options, &mut body)?;
+        writeln!(body, "</g>")?;
+        width += size.0;
+        height = height.max(size.1);
+    }
+    writeln!(
+        write,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w:.0}\" height=\"{h:.0}\" viewBox=\"-1 -1 {w:.0} {h:.0}\" \
+         font-family=\"monospace\" font-size=\"{font}\">",
+        w = width + 2.0,
+        h = height + 2.0,
+        font = FONT_SIZE
+    )?;
+    writeln!(write, "<defs>")?;
+    for color in &EDGE_COLORS {
+        writeln!(
+            write,
+            "<marker id=\"arrow-{name}\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" \
+             markerWidth=\"8\" markerHeight=\"8\" orient=\"auto\">\
+             <path d=\"M0,0 L10,5 L0,10 z\" fill=\"{color}\"/></marker>",
+            name = color,
+            color = svg_color(color)
+        )?;
+    }
+    writeln!(write, "</defs>")?;
+    write.write_all(&body)?;
+    writeln!(write, "</svg>")
+}
+
+/// Graphviz uses the X11 colors, some of which are missing or different in SVG.
+fn svg_color(color: &str) -> &str {
+    match color {
+        "gray" => "#c0c0c0",
+        "gray90" => "#e5e5e5",
+        "gray60" => "#999999",
+        "red3" => "#cd0000",
+        color => color,
+    }
+}
+
+fn text_width(line: &str) -> f64 {
+    line.chars().count() as f64 * CHAR_WIDTH
+}
+
+/// A box of the function graph: the function itself or one of its blocks.
+enum Node {
+    Function(String),
+    Block(Word),
+}
+
+impl<'spir> PetSpirv<'spir> {
+    /// Writes this function in a frame with its top left corner at the origin
+    /// and returns the size of the frame.
+    pub fn add_fn_to_svg(
+        &self,
+        options: &DotOptions,
+        write: &mut impl Write,
+    ) -> io::Result<(f64, f64)> {
+        let fn_name = self.module.get_name_fn(self.function).unwrap_or("Unknown");
+        let reachable = self.reachable_blocks();
+        let drawn = |id: &Word| !options.hide_unreachable || reachable.contains(id);
+        let mut red_blocks = HashSet::new();
+        let mut red_edges = HashSet::new();
+        if options.highlight_violations {
+            for violation in self.validate() {
+                red_blocks.extend(violation.blocks());
+                red_edges.extend(violation.edges());
+            }
+        }
+
+        let mut nodes = vec![Node::Function(fn_name.to_string())];
+        nodes.extend(
+            self.ordered_blocks(options.block_order)
+                .into_iter()
+                .filter(|id| drawn(id))
+                .map(Node::Block),
+        );
+        let indices: HashMap<Word, usize> = nodes
+            .iter()
+            .enumerate()
+            .filter_map(|(index, node)| match node {
+                Node::Block(id) => Some((*id, index)),
+                Node::Function(_) => None,
+            })
+            .collect();
+        let index_of = |id: Word| indices.get(&id).cloned();
+        // The title and the listing of every node
+        let lines: Vec<(String, Vec<ListingLine>)> = nodes
+            .iter()
+            .map(|node| match node {
+                Node::Function(name) => (name.clone(), Vec::new()),
+                Node::Block(id) => (
+                    self.module.name_or_id(Some(*id)).expect("name"),
+                    self.block_listing(*id, options.source_view, options.hide_debug_info),
+                ),
+            })
+            .collect();
+        let sizes: Vec<(f64, f64)> = lines
+            .iter()
+            .map(|(title, listing)| {
+                let width = listing
+                    .iter()
+                    .map(|line| match line {
+                        ListingLine::Instruction(text) | ListingLine::Source(text) => {
+                            text_width(text)
+                        }
+                    })
+                    .fold(text_width(title), f64::max);
+                let separator = if listing.is_empty() {
+                    0.0
+                } else {
+                    PADDING * 2.0
+                };
+                (
+                    width + PADDING * 4.0,
+                    (listing.len() + 1) as f64 * LINE_HEIGHT + PADDING * 2.0 + separator,
+                )
+            })
+            .collect();
+
+        // The edges with their source, kind and style
+        let mut edges = Vec::new();
+        if let Some(entry) = self.entry_block().and_then(index_of) {
+            edges.push((0, entry, None, NodeStyle::Normal));
+        }
+        for (source, node) in nodes.iter().enumerate() {
+            let id = match node {
+                Node::Block(id) => *id,
+                Node::Function(_) => continue,
+            };
+            let terminator = Terminator::from_basic_block(self.get_block(id));
+            for edge in terminator.edges() {
+                let target = match index_of(edge.target) {
+                    Some(target) => target,
+                    None => continue,
+                };
+                let style = if !reachable.contains(&id) {
+                    NodeStyle::Unreachable
+                } else if red_edges.contains(&(id, edge.target)) {
+                    NodeStyle::Violation
+                } else {
+                    NodeStyle::Normal
+                };
+                let label = self.edge_label(&terminator, &edge);
+                edges.push((source, target, Some((edge.kind, label)), style));
+            }
+        }
+        let pairs: Vec<(usize, usize)> = edges
+            .iter()
+            .map(|&(source, target, _, _)| (source, target))
+            .collect();
+        let graph = layout(&sizes, &pairs);
+
+        let (left, top) = (FRAME_MARGIN, FRAME_MARGIN + LINE_HEIGHT + PADDING);
+        let width = graph.width + FRAME_MARGIN * 2.0;
+        let height = graph.height + top + FRAME_MARGIN;
+        writeln!(
+            write,
+            "<rect x=\"0\" y=\"0\" width=\"{:.1}\" height=\"{:.1}\" fill=\"none\" stroke=\"black\"/>",
+            width, height
+        )?;
+        writeln!(
+            write,
+            "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>",
+            width / 2.0,
+            FRAME_MARGIN + FONT_SIZE,
+            escape_html(fn_name)
+        )?;
+        writeln!(write, "<g transform=\"translate({:.1},{:.1})\">", left, top)?;
+        for (index, node) in nodes.iter().enumerate() {
+            let (x, y) = graph.nodes[index];
+            let (w, h) = sizes[index];
+            match node {
+                Node::Function(name) => {
+                    writeln!(
+                        write,
+                        "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"white\" stroke=\"black\"/>",
+                        x, y, w, h
+                    )?;
+                    writeln!(
+                        write,
+                        "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>",
+                        x + w / 2.0,
+                        y + PADDING + FONT_SIZE,
+                        escape_html(name)
+                    )?;
+                }
+                Node::Block(id) => {
+                    let style = if red_blocks.contains(id) {
+                        NodeStyle::Violation
+                    } else if reachable.contains(id) {
+                        NodeStyle::Normal
+                    } else {
+                        NodeStyle::Unreachable
+                    };
+                    let (title, listing) = &lines[index];
+                    self.write_svg_block(*id, title, listing, (x, y, w, h), style, write)?;
+                }
+            }
+        }
+        for (index, &(source, target, ref kind, style)) in edges.iter().enumerate() {
+            let points = &graph.edges[index];
+            let (kind, label) = match kind {
+                Some((kind, label)) => (Some(kind), label.as_ref()),
+                None => (None, None),
+            };
+            let curved = source != target;
+            write_svg_edge(options, points, curved, kind, label, style, write)?;
+        }
+        writeln!(write, "</g>")?;
+        Ok((width, height))
+    }
+
+    fn write_svg_block(
+        &self,
+        id: Word,
+        title: &str,
+        listing: &[ListingLine],
+        (x, y, w, h): (f64, f64, f64, f64),
+        style: NodeStyle,
+        write: &mut impl Write,
+    ) -> io::Result<()> {
+        let terminator = Terminator::from_basic_block(self.get_block(id));
+        let (stroke, border, color) = match style {
+            NodeStyle::Normal => ("stroke=\"black\"", "", header_color(&terminator)),
+            NodeStyle::Unreachable => ("stroke=\"#999999\"", " stroke-dasharray=\"5,3\"", "gray90"),
+            NodeStyle::Violation => (
+                "stroke=\"red\" stroke-width=\"3\"",
+                "",
+                header_color(&terminator),
+            ),
+        };
+        writeln!(write, "<g>")?;
+        writeln!(
+            write,
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\"/>",
+            x + PADDING / 2.0,
+            y + PADDING / 2.0,
+            w - PADDING,
+            LINE_HEIGHT + PADDING,
+            svg_color(color)
+        )?;
+        writeln!(
+            write,
+            "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>",
+            x + w / 2.0,
+            y + PADDING + FONT_SIZE,
+            escape_html(title)
+        )?;
+        for (row, line) in listing.iter().enumerate() {
+            let (text, attributes) = match line {
+                ListingLine::Instruction(text) => (text, ""),
+                ListingLine::Source(text) => (text, " fill=\"#00008b\" font-style=\"italic\""),
+            };
+            writeln!(
+                write,
+                "<text x=\"{:.1}\" y=\"{:.1}\" xml:space=\"preserve\"{}>{}</text>",
+                x + PADDING * 2.0,
+                y + PADDING * 3.0 + FONT_SIZE + LINE_HEIGHT * (row + 1) as f64,
+                attributes,
+                escape_html(text)
+            )?;
+        }
+        writeln!(
+            write,
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"none\" {}{}/>",
+            x, y, w, h, stroke, border
+        )?;
+        writeln!(write, "</g>")
+    }
+}
+
+/// Draws an edge through its layout points with the same colors and dashes as
+/// the DOT output.
+fn write_svg_edge(
+    options: &DotOptions,
+    points: &[(f64, f64)],
+    curved: bool,
+    kind: Option<&EdgeKind>,
+    label: Option<&String>,
+    style: NodeStyle,
+    write: &mut impl Write,
+) -> io::Result<()> {
+    let (mut color, mut dashes, mut width) = match kind {
+        Some(EdgeKind::True { .. }) if options.color_branches => ("darkgreen", None, 1.0),
+        Some(EdgeKind::False { .. }) if options.color_branches => ("red3", None, 1.0),
+        Some(EdgeKind::SelectionMerge) => ("black", Some("5,3"), 1.0),
+        Some(EdgeKind::LoopMerge) => ("blue", Some("5,3"), 1.0),
+        Some(EdgeKind::Continue) => ("blue", Some("1,3"), 1.0),
+        Some(EdgeKind::Case { default: true, .. }) => ("black", None, 2.0),
+        _ => ("black", None, 1.0),
+    };
+    match style {
+        NodeStyle::Normal => {}
+        NodeStyle::Unreachable => {
+            color = "gray60";
+            dashes = Some("5,3");
+            width = 1.0;
+        }
+        NodeStyle::Violation => {
+            color = "red";
+            width = 2.0;
+        }
+    }
+
+    // Curves leave and enter the nodes vertically, loops back to the same node
+    // are drawn with straight lines
+    let mut path = format!("M{:.1},{:.1}", points[0].0, points[0].1);
+    for pair in points.windows(2) {
+        let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+        if curved {
+            let middle = (y0 + y1) / 2.0;
+            path.push_str(&format!(
+                " C{:.1},{:.1} {:.1},{:.1} {:.1},{:.1}",
+                x0, middle, x1, middle, x1, y1
+            ));
+        } else {
+            path.push_str(&format!(" L{:.1},{:.1}", x1, y1));
+        }
+    }
+    writeln!(
+        write,
+        "<path d=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{}\"{} marker-end=\"url(#arrow-{})\"/>",
+        path,
+        svg_color(color),
+        width,
+        dashes
+            .map(|dashes| format!(" stroke-dasharray=\"{}\"", dashes))
+            .unwrap_or_default(),
+        color
+    )?;
+    if let Some(label) = label {
+        let middle = points.len() / 2;
+        let (x, y) = (
+            (points[middle - 1].0 + points[middle].0) / 2.0,
+            (points[middle - 1].1 + points[middle].1) / 2.0,
+        );
+        let fill = if style == NodeStyle::Unreachable {
+            svg_color("gray60")
+        } else {
+            "black"
+        };
+        writeln!(
+            write,
+            "<text x=\"{:.1}\" y=\"{:.1}\" fill=\"{}\" stroke=\"white\" stroke-width=\"3\" paint-order=\"stroke\">{}</text>",
+            x + PADDING,
+            y + FONT_SIZE / 3.0,
+            fill,
+            escape_html(label)
+        )?;
+    }
+    Ok(())
+}
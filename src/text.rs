@@ -0,0 +1,332 @@
+//! Plain text output for terminals.
+//!
+//! Every block is drawn as a box followed by its outgoing edges. Blocks inside
+//! a structured selection or loop are indented below their header, so the
+//! nesting declared by `OpSelectionMerge` and `OpLoopMerge` can be read from the
+//! indentation alone.
+use super::mermaid::truncate;
+use super::{
+    opname, CallGraph, EdgeKind, FunctionFilter, ListingLine, PetSpirv, SourceView, SpirvModule,
+    Terminator,
+};
+use spirv::Word;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Controls what ends up in the text output.
+#[derive(Clone, Debug, Default)]
+pub struct TextOptions {
+    /// Draw with ASCII characters only instead of Unicode box drawing characters.
+    pub ascii: bool,
+    /// Show at most this many instructions per block.
+    pub max_instructions: Option<usize>,
+    /// Cut instructions longer than this many characters.
+    pub max_line_width: Option<usize>,
+    /// Show the source lines of blocks with, or instead of, their instructions.
+    pub source_view: SourceView,
+    /// Leave the shader debug info instructions out of the blocks, they still
+    /// provide source lines and scopes.
+    pub hide_debug_info: bool,
+    /// The functions to draw, all of them if the filter is empty.
+    pub functions: FunctionFilter,
+}
+
+/// The characters used to draw boxes and edges.
+struct Glyphs {
+    horizontal: char,
+    vertical: char,
+    top_left: char,
+    top_right: char,
+    bottom_left: char,
+    bottom_right: char,
+    /// The connector of every edge but the last one
+    tee: &'static str,
+    /// The connector of the last edge
+    elbow: &'static str,
+    /// Line of a branch
+    solid: char,
+    /// Line of a merge or continue declaration
+    dashed: char,
+    arrow: char,
+    ellipsis: char,
+}
+
+const UNICODE: Glyphs = Glyphs {
+    horizontal: '─',
+    vertical: '│',
+    top_left: '┌',
+    top_right: '┐',
+    bottom_left: '└',
+    bottom_right: '┘',
+    tee: "├",
+    elbow: "└",
+    solid: '─',
+    dashed: '┄',
+    arrow: '▶',
+    ellipsis: '…',
+};
+
+const ASCII: Glyphs = Glyphs {
+    horizontal: '-',
+    vertical: '|',
+    top_left: '+',
+    top_right: '+',
+    bottom_left: '+',
+    bottom_right: '+',
+    tee: "|",
+    elbow: "`",
+    solid: '-',
+    dashed: '.',
+    arrow: '>',
+    ellipsis: '~',
+};
+
+/// Writes the control flow graph of every selected function as text to `path`.
+pub fn export_text<P: AsRef<Path>>(
+    module: &SpirvModule,
+    options: &TextOptions,
+    path: P,
+) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    write_text(module, &CallGraph::new(module), options, &mut file)?;
+    file.flush()
+}
+
+/// Writes the control flow graph of every selected function as text into `write`.
+pub fn write_text(
+    module: &SpirvModule,
+    graph: &CallGraph,
+    options: &TextOptions,
+    write: &mut impl Write,
+) -> io::Result<()> {
+    for (index, f) in options
+        .functions
+        .select(module, graph)
+        .into_iter()
+        .enumerate()
+    {
+        if index > 0 {
+            writeln!(write)?;
+        }
+        let pet = PetSpirv::new(module, f);
+        let fn_id = pet.function_id();
+        let fn_name = module.name_or_id(Some(fn_id)).expect("name");
+        let entry_points: Vec<String> = graph
+            .entry_points_of(fn_id)
+            .iter()
+            .map(|entry_point| format!(" [{:?} {}]", entry_point.execution_model, entry_point.name))
+            .collect();
+        writeln!(write, "function {}{}", fn_name, entry_points.concat())?;
+        pet.write_text(options, write)?;
+    }
+    Ok(())
+}
+
+impl<'spir> PetSpirv<'spir> {
+    /// The header of the innermost structured construct containing every
+    /// block, `None` for blocks outside of all constructs. A construct is made
+    /// of the blocks dominated by its header but not by its merge block.
+    fn construct_parents(&self) -> HashMap<Word, Option<Word>> {
+        let dominators = self.dominator_tree();
+        let merges: HashMap<Word, Word> = self
+            .block_map
+            .iter()
+            .filter_map(|(&id, block)| {
+                let merge = Terminator::from_basic_block(block).merge_block()?;
+                Some((id, merge))
+            })
+            .collect();
+        // Preorder over the dominator tree, a child is in the construct of its
+        // parent if that is a header, else in its parent's construct, minus
+        // the constructs it is the merge block of
+        let mut parents: HashMap<Word, Option<Word>> =
+            self.block_map.keys().map(|&id| (id, None)).collect();
+        let mut stack = dominators.roots();
+        while let Some(id) = stack.pop() {
+            let children = dominators.children(id);
+            for &child in &children {
+                let mut parent = if merges.contains_key(&id) {
+                    Some(id)
+                } else {
+                    parents[&id]
+                };
+                while let Some(header) = parent {
+                    if !dominators.dominates(merges[&header], child) {
+                        break;
+                    }
+                    parent = parents[&header];
+                }
+                parents.insert(child, parent);
+            }
+            stack.extend(children.into_iter().rev());
+        }
+        parents
+    }
+
+    /// Writes the blocks of this function, nested by structured construct.
+    pub fn write_text(&self, options: &TextOptions, write: &mut impl Write) -> io::Result<()> {
+        let glyphs = if options.ascii { &ASCII } else { &UNICODE };
+        let reachable = self.reachable_blocks();
+        let parents = self.construct_parents();
+        // Reachable blocks in reverse postorder, then the rest in layout order
+        let mut children: BTreeMap<Option<Word>, Vec<Word>> = BTreeMap::new();
+        for id in self.reverse_postorder() {
+            children.entry(parents[&id]).or_default().push(id);
+        }
+        let mut unreachable = Vec::new();
+        for block in &self.function.blocks {
+            if let Some(id) = block.label.as_ref().and_then(|label| label.result_id) {
+                if !reachable.contains(&id) {
+                    unreachable.push(id);
+                }
+            }
+        }
+        let back_edges: HashSet<(Word, Word)> = self.back_edges().into_iter().collect();
+
+        // Depth first over the construct tree, the stack holds the blocks still
+        // to be written on every nesting level
+        let mut stack = vec![children
+            .get(&None)
+            .cloned()
+            .unwrap_or_default()
+            .into_iter()
+            .chain(unreachable)
+            .collect::<Vec<_>>()
+            .into_iter()];
+        while let Some(level) = stack.last_mut() {
+            let id = match level.next() {
+                Some(id) => id,
+                None => {
+                    stack.pop();
+                    continue;
+                }
+            };
+            let indent: String = (1..stack.len())
+                .map(|_| format!("{} ", glyphs.vertical))
+                .collect();
+            self.write_text_block(
+                id,
+                &indent,
+                reachable.contains(&id),
+                &back_edges,
+                options,
+                write,
+            )?;
+            if let Some(nested) = children.get(&Some(id)) {
+                stack.push(nested.clone().into_iter());
+            }
+        }
+        Ok(())
+    }
+
+    fn write_text_block(
+        &self,
+        id: Word,
+        indent: &str,
+        reachable: bool,
+        back_edges: &HashSet<(Word, Word)>,
+        options: &TextOptions,
+        write: &mut impl Write,
+    ) -> io::Result<()> {
+        let glyphs = if options.ascii { &ASCII } else { &UNICODE };
+        let block = self.get_block(id);
+        let mut name = self.module.name_or_id(Some(id)).expect("name");
+        if !reachable {
+            name.push_str(" (unreachable)");
+        }
+        let mut lines = vec![name];
+        let listing = self.block_listing(id, options.source_view, options.hide_debug_info);
+        let shown = options
+            .max_instructions
+            .unwrap_or(listing.len())
+            .min(listing.len());
+        for line in &listing[..shown] {
+            let text = match line {
+                ListingLine::Instruction(text) => text.clone(),
+                ListingLine::Source(text) => format!("// {}", text),
+            };
+            lines.push(truncate(text, options.max_line_width, glyphs.ellipsis));
+        }
+        if shown < listing.len() {
+            lines.push(format!(
+                "{} {} more",
+                glyphs.ellipsis,
+                listing.len() - shown
+            ));
+        }
+        let width = lines
+            .iter()
+            .map(|line| line.chars().count())
+            .max()
+            .unwrap_or(0);
+        let rule: String = (0..width + 2).map(|_| glyphs.horizontal).collect();
+        writeln!(
+            write,
+            "{}{}{}{}",
+            indent, glyphs.top_left, rule, glyphs.top_right
+        )?;
+        for line in &lines {
+            writeln!(
+                write,
+                "{}{} {}{} {}",
+                indent,
+                glyphs.vertical,
+                line,
+                " ".repeat(width - line.chars().count()),
+                glyphs.vertical
+            )?;
+        }
+        writeln!(
+            write,
+            "{}{}{}{}",
+            indent, glyphs.bottom_left, rule, glyphs.bottom_right
+        )?;
+
+        let terminator = Terminator::from_basic_block(block);
+        let mut edges: Vec<String> = terminator
+            .edges()
+            .iter()
+            .map(|edge| {
+                let line = match edge.kind {
+                    EdgeKind::SelectionMerge | EdgeKind::LoopMerge | EdgeKind::Continue => {
+                        glyphs.dashed
+                    }
+                    _ => glyphs.solid,
+                };
+                let label = match edge.kind {
+                    EdgeKind::SelectionMerge => Some("merge".to_string()),
+                    _ => self.edge_label(&terminator, edge),
+                };
+                let mut text = match label {
+                    Some(label) => format!("{} {} {}{}{} ", line, label, line, line, glyphs.arrow),
+                    None => format!("{}{}{}{} ", line, line, line, glyphs.arrow),
+                };
+                text.push_str(&self.module.name_or_id(Some(edge.target)).expect("name"));
+                if back_edges.contains(&(id, edge.target)) {
+                    text.push_str(" (back edge)");
+                }
+                text
+            })
+            .collect();
+        if terminator.is_exit() {
+            let exit = match block.instructions.last() {
+                Some(inst) if terminator != Terminator::Missing => {
+                    format!("Op{}", opname(inst))
+                }
+                _ => "no terminator".to_string(),
+            };
+            edges.push(format!("{} {}", glyphs.solid, exit));
+        }
+        for (index, edge) in edges.iter().enumerate() {
+            let connector = if index + 1 < edges.len() {
+                glyphs.tee
+            } else {
+                glyphs.elbow
+            };
+            writeln!(write, "{}  {}{}", indent, connector, edge)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,212 @@
+//! Depth first traversals of the CFG.
+//!
+//! The traversals keep their own stack instead of recursing, so they work on
+//! functions of any size. Successors are visited in the order the terminator
+//! lists them, and only blocks reachable from the entry block are visited.
+//!
+//! The search itself works on any [`Graph`], the dominator trees, the loop
+//! detection and the layout build on it as well.
+use super::PetSpirv;
+use spirv::Word;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::vec;
+
+/// A directed graph the depth first search can walk.
+pub(crate) trait Graph {
+    type Node: Copy + Eq + Hash;
+    fn successors(&self, node: Self::Node) -> Vec<Self::Node>;
+}
+
+impl<'a, 'spir> Graph for &'a PetSpirv<'spir> {
+    type Node = Word;
+    fn successors(&self, id: Word) -> Vec<Word> {
+        PetSpirv::successors(self, id)
+    }
+}
+
+/// Successor lists keyed by node.
+impl Graph for &HashMap<Word, Vec<Word>> {
+    type Node = Word;
+    fn successors(&self, id: Word) -> Vec<Word> {
+        self.get(&id).cloned().unwrap_or_default()
+    }
+}
+
+/// Successor lists indexed by node.
+impl Graph for &[Vec<usize>] {
+    type Node = usize;
+    fn successors(&self, node: usize) -> Vec<usize> {
+        self[node].clone()
+    }
+}
+
+/// A node on the search stack with its successors and the index of the next
+/// successor to look at.
+type Frame<N> = (N, Vec<N>, usize);
+
+/// A depth first search starting at every root that hasn't been visited yet,
+/// in order.
+pub(crate) struct DepthFirst<G: Graph> {
+    graph: G,
+    roots: vec::IntoIter<G::Node>,
+    stack: Vec<Frame<G::Node>>,
+    visited: HashSet<G::Node>,
+    on_stack: HashSet<G::Node>,
+}
+
+pub(crate) enum Event<N> {
+    Enter(N),
+    Leave(N),
+    /// An edge `(source, target)` to a node that is still on the stack, self
+    /// loops included.
+    BackEdge(N, N),
+}
+
+impl<G: Graph> DepthFirst<G> {
+    pub(crate) fn new(graph: G, roots: Vec<G::Node>) -> Self {
+        DepthFirst {
+            graph,
+            roots: roots.into_iter(),
+            stack: Vec::new(),
+            visited: HashSet::new(),
+            on_stack: HashSet::new(),
+        }
+    }
+
+    fn enter(&mut self, node: G::Node) -> Event<G::Node> {
+        self.visited.insert(node);
+        self.on_stack.insert(node);
+        self.stack.push((node, self.graph.successors(node), 0));
+        Event::Enter(node)
+    }
+}
+
+impl<G: Graph> Iterator for DepthFirst<G> {
+    type Item = Event<G::Node>;
+    fn next(&mut self) -> Option<Event<G::Node>> {
+        loop {
+            let (source, target) = match self.stack.last_mut() {
+                Some(&mut (node, ref successors, ref mut next)) => match successors.get(*next) {
+                    Some(&target) => {
+                        *next += 1;
+                        (node, target)
+                    }
+                    None => {
+                        self.stack.pop();
+                        self.on_stack.remove(&node);
+                        return Some(Event::Leave(node));
+                    }
+                },
+                None => {
+                    let root = self.roots.next()?;
+                    if self.visited.contains(&root) {
+                        continue;
+                    }
+                    return Some(self.enter(root));
+                }
+            };
+            if self.on_stack.contains(&target) {
+                return Some(Event::BackEdge(source, target));
+            }
+            if !self.visited.contains(&target) {
+                return Some(self.enter(target));
+            }
+        }
+    }
+}
+
+/// Iterator over the reachable blocks in depth first preorder.
+pub struct Preorder<'a, 'spir: 'a>(DepthFirst<&'a PetSpirv<'spir>>);
+
+impl<'a, 'spir> Iterator for Preorder<'a, 'spir> {
+    type Item = Word;
+    fn next(&mut self) -> Option<Word> {
+        loop {
+            if let Event::Enter(id) = self.0.next()? {
+                return Some(id);
+            }
+        }
+    }
+}
+
+/// Iterator over the reachable blocks in depth first postorder.
+pub struct Postorder<'a, 'spir: 'a>(DepthFirst<&'a PetSpirv<'spir>>);
+
+impl<'a, 'spir> Iterator for Postorder<'a, 'spir> {
+    type Item = Word;
+    fn next(&mut self) -> Option<Word> {
+        loop {
+            if let Event::Leave(id) = self.0.next()? {
+                return Some(id);
+            }
+        }
+    }
+}
+
+impl<'spir> PetSpirv<'spir> {
+    fn depth_first<'a>(&'a self) -> DepthFirst<&'a PetSpirv<'spir>> {
+        DepthFirst::new(self, self.entry_block().into_iter().collect())
+    }
+
+    /// The reachable blocks in depth first preorder, starting with the entry block.
+    pub fn preorder<'a>(&'a self) -> Preorder<'a, 'spir> {
+        Preorder(self.depth_first())
+    }
+
+    /// The reachable blocks in depth first postorder, ending with the entry block.
+    pub fn postorder<'a>(&'a self) -> Postorder<'a, 'spir> {
+        Postorder(self.depth_first())
+    }
+
+    /// All edges that jump back to a block on the depth first search stack.
+    pub(crate) fn back_edges(&self) -> Vec<(Word, Word)> {
+        self.depth_first()
+            .filter_map(|event| match event {
+                Event::BackEdge(source, target) => Some((source, target)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The reachable blocks in reverse postorder. Every block comes before its
+    /// successors, apart from the targets of back edges.
+    pub fn reverse_postorder(&self) -> Vec<Word> {
+        let mut order: Vec<Word> = self.postorder().collect();
+        order.reverse();
+        order
+    }
+}
+
+/// The order blocks are listed in by the renderers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BlockOrder {
+    /// The order of the blocks in the module
+    #[default]
+    Layout,
+    /// Reverse postorder, unreachable blocks follow in layout order
+    ReversePostorder,
+    /// Ascending result id
+    Id,
+}
+
+impl<'spir> PetSpirv<'spir> {
+    /// Every block of the function in the given order.
+    pub fn ordered_blocks(&self, order: BlockOrder) -> Vec<Word> {
+        let layout = self
+            .function
+            .blocks
+            .iter()
+            .filter_map(|block| block.label.as_ref()?.result_id);
+        match order {
+            BlockOrder::Layout => layout.collect(),
+            BlockOrder::ReversePostorder => {
+                let mut blocks = self.reverse_postorder();
+                let reachable: HashSet<Word> = blocks.iter().cloned().collect();
+                blocks.extend(layout.filter(|id| !reachable.contains(id)));
+                blocks
+            }
+            BlockOrder::Id => self.block_map.keys().cloned().collect(),
+        }
+    }
+}
@@ -0,0 +1,419 @@
+//! Structured control flow rules.
+//!
+//! Constructs follow the definitions of the SPIR-V 1.6 spec, which are based on
+//! structural dominance: merge and continue declarations count as edges, so a
+//! loop's merge block reached through a break from inside a selection is not
+//! part of that selection. A selection construct contains the blocks
+//! structurally dominated by its header minus the blocks structurally dominated
+//! by its merge block, a loop construct additionally leaves out the continue
+//! construct, which contains the blocks structurally dominated by the continue
+//! target minus the blocks structurally dominated by the loop's merge block.
+//! Unreachable blocks are exempt from all rules.
+use super::{Merge, PetSpirv, SpirvModule, Terminator};
+use spirv::Word;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+/// A structured control flow rule broken by a function.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Violation {
+    /// A branch targets an id that is not a block of the function.
+    UnknownBranchTarget { block: Word, target: Word },
+    /// The merge block declared by a header is not a block of the function.
+    UnknownMergeBlock { header: Word, merge: Word },
+    /// The continue target declared by a loop header is not a block of the function.
+    UnknownContinueTarget { header: Word, continue_target: Word },
+    /// A header doesn't strictly dominate its reachable merge block.
+    MergeNotDominated { header: Word, merge: Word },
+    /// A loop header doesn't dominate its reachable continue target.
+    ContinueNotDominated { header: Word, continue_target: Word },
+    /// A loop declares the same block as merge block and continue target.
+    MergeIsContinue { header: Word, block: Word },
+    /// Several headers declare the same merge block.
+    SharedMergeBlock { merge: Word, headers: Vec<Word> },
+    /// A back edge targets a block without `OpLoopMerge`.
+    BackEdgeToNonHeader { source: Word, target: Word },
+    /// A loop header is the target of more than one back edge.
+    MultipleBackEdges { header: Word, sources: Vec<Word> },
+    /// A back edge doesn't come from a block dominated by the continue target.
+    BackEdgeOutsideContinue { header: Word, source: Word },
+    /// A conditional branch or switch picks between several blocks that are
+    /// not breaks or continues, but doesn't declare a merge.
+    MissingMerge { block: Word },
+    /// An edge enters a construct somewhere other than at its header.
+    EntersConstruct {
+        header: Word,
+        source: Word,
+        target: Word,
+    },
+    /// An edge leaves a construct to a block that is not the merge block of
+    /// the innermost construct, the merge block or continue target of the
+    /// innermost loop or the merge block of the innermost switch.
+    ExitsConstruct {
+        header: Word,
+        source: Word,
+        target: Word,
+    },
+}
+
+impl Violation {
+    /// The blocks to blame for the violation.
+    pub fn blocks(&self) -> Vec<Word> {
+        match self {
+            Violation::UnknownBranchTarget { block, .. } | Violation::MissingMerge { block } => {
+                vec![*block]
+            }
+            Violation::UnknownMergeBlock { header, .. }
+            | Violation::UnknownContinueTarget { header, .. }
+            | Violation::MergeIsContinue { header, .. } => vec![*header],
+            Violation::MergeNotDominated { header, merge } => vec![*header, *merge],
+            Violation::ContinueNotDominated {
+                header,
+                continue_target,
+            } => vec![*header, *continue_target],
+            Violation::SharedMergeBlock { merge, headers } => {
+                Some(*merge).into_iter().chain(headers.clone()).collect()
+            }
+            Violation::MultipleBackEdges { header, sources } => {
+                Some(*header).into_iter().chain(sources.clone()).collect()
+            }
+            Violation::BackEdgeToNonHeader { source, target }
+            | Violation::EntersConstruct { source, target, .. }
+            | Violation::ExitsConstruct { source, target, .. } => vec![*source, *target],
+            Violation::BackEdgeOutsideContinue { header, source } => vec![*source, *header],
+        }
+    }
+
+    /// The edges `(source, target)` to blame for the violation.
+    pub fn edges(&self) -> Vec<(Word, Word)> {
+        match self {
+            Violation::MergeNotDominated { header, merge } => vec![(*header, *merge)],
+            Violation::ContinueNotDominated {
+                header,
+                continue_target,
+            } => vec![(*header, *continue_target)],
+            Violation::MultipleBackEdges { header, sources } => {
+                sources.iter().map(|&source| (source, *header)).collect()
+            }
+            Violation::BackEdgeToNonHeader { source, target }
+            | Violation::EntersConstruct { source, target, .. }
+            | Violation::ExitsConstruct { source, target, .. } => vec![(*source, *target)],
+            Violation::BackEdgeOutsideContinue { header, source } => vec![(*source, *header)],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Describes the violation, referring to blocks by name where they have one.
+    pub fn message(&self, module: &SpirvModule) -> String {
+        let name = |id: &Word| module.name_or_id(Some(*id)).expect("name");
+        let names = |ids: &[Word]| ids.iter().map(name).collect::<Vec<_>>().join(", ");
+        match self {
+            Violation::UnknownBranchTarget { block, target } => format!(
+                "{} branches to {} which is not a block of the function",
+                name(block),
+                name(target)
+            ),
+            Violation::UnknownMergeBlock { header, merge } => format!(
+                "merge block {} of {} is not a block of the function",
+                name(merge),
+                name(header)
+            ),
+            Violation::UnknownContinueTarget {
+                header,
+                continue_target,
+            } => format!(
+                "continue target {} of {} is not a block of the function",
+                name(continue_target),
+                name(header)
+            ),
+            Violation::MergeNotDominated { header, merge } => format!(
+                "header {} doesn't strictly dominate its merge block {}",
+                name(header),
+                name(merge)
+            ),
+            Violation::ContinueNotDominated {
+                header,
+                continue_target,
+            } => format!(
+                "loop header {} doesn't dominate its continue target {}",
+                name(header),
+                name(continue_target)
+            ),
+            Violation::MergeIsContinue { header, block } => format!(
+                "loop header {} uses {} as merge block and continue target",
+                name(header),
+                name(block)
+            ),
+            Violation::SharedMergeBlock { merge, headers } => format!(
+                "{} is the merge block of several headers: {}",
+                name(merge),
+                names(headers)
+            ),
+            Violation::BackEdgeToNonHeader { source, target } => format!(
+                "back edge {} -> {} targets a block without OpLoopMerge",
+                name(source),
+                name(target)
+            ),
+            Violation::MultipleBackEdges { header, sources } => format!(
+                "loop header {} has several back edges, from {}",
+                name(header),
+                names(sources)
+            ),
+            Violation::BackEdgeOutsideContinue { header, source } => format!(
+                "back edge {} -> {} doesn't come from the continue construct",
+                name(source),
+                name(header)
+            ),
+            Violation::MissingMerge { block } => format!(
+                "{} branches to several blocks but declares no merge",
+                name(block)
+            ),
+            Violation::EntersConstruct {
+                header,
+                source,
+                target,
+            } => format!(
+                "edge {} -> {} enters the construct of {} past its header",
+                name(source),
+                name(target),
+                name(header)
+            ),
+            Violation::ExitsConstruct {
+                header,
+                source,
+                target,
+            } => format!(
+                "edge {} -> {} leaves the construct of {} to a block that is not a merge block or continue target",
+                name(source),
+                name(target),
+                name(header)
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ConstructKind {
+    Selection,
+    Switch,
+    Loop,
+    Continue,
+}
+
+/// A selection, switch, loop or continue construct.
+struct Construct {
+    kind: ConstructKind,
+    header: Word,
+    blocks: HashSet<Word>,
+    /// Targets outside of the construct its blocks may branch to: the merge
+    /// block, for loops also the continue target and for continue constructs
+    /// the loop header and merge block
+    exits: Vec<Word>,
+}
+
+impl<'spir> PetSpirv<'spir> {
+    /// Checks the structured control flow rules and returns every violation.
+    pub fn validate(&self) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        let dominators = self.dominator_tree();
+        let structural = self.structural_dominator_tree();
+        let reachable: Vec<Word> = self
+            .block_map
+            .keys()
+            .cloned()
+            .filter(|&id| dominators.contains(id))
+            .collect();
+        let terminators: BTreeMap<Word, Terminator> = reachable
+            .iter()
+            .map(|&id| (id, Terminator::from_basic_block(self.get_block(id))))
+            .collect();
+
+        for (&id, terminator) in &terminators {
+            for target in terminator.successors() {
+                if !self.block_map.contains_key(&target) {
+                    violations.push(Violation::UnknownBranchTarget { block: id, target });
+                }
+            }
+        }
+
+        let mut constructs = Vec::new();
+        let mut merge_headers: BTreeMap<Word, Vec<Word>> = BTreeMap::new();
+        for (&header, terminator) in &terminators {
+            let merge = match terminator.merge() {
+                Some(merge) => merge,
+                None => continue,
+            };
+            let merge_block = merge.merge_block();
+            if !self.block_map.contains_key(&merge_block) {
+                violations.push(Violation::UnknownMergeBlock {
+                    header,
+                    merge: merge_block,
+                });
+                continue;
+            }
+            merge_headers.entry(merge_block).or_default().push(header);
+            if dominators.contains(merge_block)
+                && !dominators.strictly_dominates(header, merge_block)
+            {
+                violations.push(Violation::MergeNotDominated {
+                    header,
+                    merge: merge_block,
+                });
+            }
+            let mut blocks: HashSet<Word> = reachable
+                .iter()
+                .cloned()
+                .filter(|&id| {
+                    structural.dominates(header, id) && !structural.dominates(merge_block, id)
+                })
+                .collect();
+            let mut exits = vec![merge_block];
+            if let Merge::Loop {
+                continue_target, ..
+            } = merge
+            {
+                exits.push(continue_target);
+                if !self.block_map.contains_key(&continue_target) {
+                    violations.push(Violation::UnknownContinueTarget {
+                        header,
+                        continue_target,
+                    });
+                } else if continue_target == merge_block {
+                    violations.push(Violation::MergeIsContinue {
+                        header,
+                        block: merge_block,
+                    });
+                } else if dominators.contains(continue_target)
+                    && !dominators.dominates(header, continue_target)
+                {
+                    violations.push(Violation::ContinueNotDominated {
+                        header,
+                        continue_target,
+                    });
+                } else if continue_target != header {
+                    // A loop that is its own continue target has no separate
+                    // continue construct
+                    let (continue_blocks, loop_blocks) = blocks
+                        .into_iter()
+                        .partition(|&id| structural.dominates(continue_target, id));
+                    blocks = loop_blocks;
+                    constructs.push(Construct {
+                        kind: ConstructKind::Continue,
+                        header: continue_target,
+                        blocks: continue_blocks,
+                        exits: vec![header, merge_block],
+                    });
+                }
+            }
+            let kind = match (merge, terminator) {
+                (Merge::Loop { .. }, _) => ConstructKind::Loop,
+                (_, Terminator::Switch { .. }) => ConstructKind::Switch,
+                _ => ConstructKind::Selection,
+            };
+            constructs.push(Construct {
+                kind,
+                header,
+                blocks,
+                exits,
+            });
+        }
+        for (merge, headers) in merge_headers {
+            if headers.len() > 1 {
+                violations.push(Violation::SharedMergeBlock { merge, headers });
+            }
+        }
+
+        let mut back_edges: BTreeMap<Word, Vec<Word>> = BTreeMap::new();
+        for (source, target) in self.back_edges() {
+            back_edges.entry(target).or_default().push(source);
+        }
+        for (header, sources) in back_edges {
+            let continue_target = match terminators.get(&header).and_then(|t| t.merge()) {
+                Some(Merge::Loop {
+                    continue_target, ..
+                }) => continue_target,
+                _ => {
+                    for source in sources {
+                        violations.push(Violation::BackEdgeToNonHeader {
+                            source,
+                            target: header,
+                        });
+                    }
+                    continue;
+                }
+            };
+            for &source in &sources {
+                if dominators.contains(continue_target)
+                    && !dominators.dominates(continue_target, source)
+                {
+                    violations.push(Violation::BackEdgeOutsideContinue { header, source });
+                }
+            }
+            if sources.len() > 1 {
+                violations.push(Violation::MultipleBackEdges { header, sources });
+            }
+        }
+
+        // Smallest constructs first so edges are blamed on the innermost one
+        constructs.sort_by_key(|construct| construct.blocks.len());
+        for (&source, terminator) in &terminators {
+            // Breaks are only allowed out of the innermost construct, loop and
+            // switch
+            let innermost = |kinds: &[ConstructKind]| {
+                constructs.iter().find(|construct| {
+                    kinds.contains(&construct.kind) && construct.blocks.contains(&source)
+                })
+            };
+            let allowed: BTreeSet<Word> = [
+                innermost(&[
+                    ConstructKind::Selection,
+                    ConstructKind::Switch,
+                    ConstructKind::Loop,
+                    ConstructKind::Continue,
+                ]),
+                innermost(&[ConstructKind::Loop, ConstructKind::Continue]),
+                innermost(&[ConstructKind::Switch]),
+            ]
+            .iter()
+            .flatten()
+            .flat_map(|construct| construct.exits.iter().cloned())
+            .collect();
+            let targets = self.successors(source);
+            if terminator.merge().is_none()
+                && targets.iter().filter(|t| !allowed.contains(t)).count() > 1
+            {
+                violations.push(Violation::MissingMerge { block: source });
+            }
+            for target in targets {
+                if !dominators.contains(target) {
+                    continue;
+                }
+                let entered = constructs.iter().find(|construct| {
+                    target != construct.header
+                        && construct.blocks.contains(&target)
+                        && !construct.blocks.contains(&source)
+                });
+                if let Some(construct) = entered {
+                    violations.push(Violation::EntersConstruct {
+                        header: construct.header,
+                        source,
+                        target,
+                    });
+                    continue;
+                }
+                let exited = constructs.iter().find(|construct| {
+                    construct.blocks.contains(&source) && !construct.blocks.contains(&target)
+                });
+                if let Some(construct) = exited {
+                    if !allowed.contains(&target) {
+                        violations.push(Violation::ExitsConstruct {
+                            header: construct.header,
+                            source,
+                            target,
+                        });
+                    }
+                }
+            }
+        }
+        violations
+    }
+}
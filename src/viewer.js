@@ -0,0 +1,386 @@
+// Viewer for the CFG embedded as JSON in the `cfg-data` script tag. Blocks
+// are placed in layers along the forward edges, back edges and merge
+// declarations are drawn on top of that layout.
+(function () {
+  "use strict";
+  var data = JSON.parse(document.getElementById("cfg-data").textContent);
+  var SVG = "http://www.w3.org/2000/svg";
+  var LINE = 16;
+  var PAD = 6;
+  var RANK_GAP = 48;
+  var NODE_GAP = 28;
+  var FLOW = { branch: true, "true": true, "false": true, "case": true };
+
+  var measure = document.createElement("canvas").getContext("2d");
+  measure.font = "12px monospace";
+  var CHAR = measure.measureText("0123456789").width / 10;
+
+  var svg = document.getElementById("graph");
+  var scene = document.getElementById("scene");
+  var viewport = document.getElementById("viewport");
+  var picker = document.getElementById("function");
+  var view = { x: 20, y: 20, k: 1 };
+  var current = null;
+  var expanded = {};
+
+  function el(name, attributes, parent) {
+    var node = document.createElementNS(SVG, name);
+    for (var key in attributes) {
+      node.setAttribute(key, attributes[key]);
+    }
+    if (parent) {
+      parent.appendChild(node);
+    }
+    return node;
+  }
+
+  function nameOf(id) {
+    return data.names[id] !== undefined ? data.names[id] + "(%" + id + ")" : "%" + id;
+  }
+
+  function lines(block) {
+    var text = [nameOf(block.id)];
+    if (expanded[block.id]) {
+      block.instructions.forEach(function (inst) {
+        text.push(inst.text);
+      });
+    } else {
+      text.push("▸ " + block.instructions.length + " instructions");
+    }
+    return text;
+  }
+
+  // Assigns every block a rank along the forward edges and an order within
+  // its rank, then turns both into coordinates.
+  function layout(fn) {
+    var blocks = {};
+    var succs = {};
+    var preds = {};
+    fn.blocks.forEach(function (block) {
+      blocks[block.id] = block;
+      succs[block.id] = [];
+      preds[block.id] = [];
+    });
+    fn.edges.forEach(function (edge) {
+      if (FLOW[edge.kind] && blocks[edge.target] && succs[edge.source].indexOf(edge.target) < 0) {
+        succs[edge.source].push(edge.target);
+      }
+    });
+
+    // Depth first search from the entry block, then from the unreachable rest
+    var state = {};
+    var postorder = [];
+    var back = {};
+    var roots = fn.entry_block !== null ? [fn.entry_block] : [];
+    fn.blocks.forEach(function (block) {
+      roots.push(block.id);
+    });
+    roots.forEach(function (root) {
+      if (state[root]) {
+        return;
+      }
+      var stack = [[root, 0]];
+      state[root] = 1;
+      while (stack.length) {
+        var top = stack[stack.length - 1];
+        var next = succs[top[0]][top[1]++];
+        if (next === undefined) {
+          state[top[0]] = 2;
+          postorder.push(top[0]);
+          stack.pop();
+        } else if (state[next] === 1) {
+          back[top[0] + ":" + next] = true;
+        } else if (!state[next]) {
+          state[next] = 1;
+          stack.push([next, 0]);
+        }
+      }
+    });
+    var order = postorder.reverse();
+    order.forEach(function (id) {
+      succs[id].forEach(function (target) {
+        if (!back[id + ":" + target]) {
+          preds[target].push(id);
+        }
+      });
+    });
+
+    var rank = {};
+    var layers = [];
+    order.forEach(function (id) {
+      var r = 0;
+      preds[id].forEach(function (pred) {
+        r = Math.max(r, rank[pred] + 1);
+      });
+      rank[id] = r;
+      (layers[r] = layers[r] || []).push(id);
+    });
+
+    // Barycenter sweeps to reduce crossings
+    var position = {};
+    function index() {
+      layers.forEach(function (layer) {
+        layer.forEach(function (id, i) {
+          position[id] = i;
+        });
+      });
+    }
+    function sweep(neighbours) {
+      return function (layer) {
+        var weight = {};
+        layer.forEach(function (id) {
+          var ns = neighbours(id);
+          weight[id] = ns.length
+            ? ns.reduce(function (sum, n) { return sum + position[n]; }, 0) / ns.length
+            : position[id];
+        });
+        layer.sort(function (a, b) { return weight[a] - weight[b]; });
+        layer.forEach(function (id, i) {
+          position[id] = i;
+        });
+      };
+    }
+    var forwardSuccs = function (id) {
+      return succs[id].filter(function (t) { return !back[id + ":" + t]; });
+    };
+    index();
+    for (var i = 0; i < 4; i++) {
+      layers.slice(1).forEach(sweep(function (id) { return preds[id]; }));
+      layers.slice(0, -1).reverse().forEach(sweep(forwardSuccs));
+    }
+
+    var boxes = {};
+    var y = 0;
+    var width = 0;
+    layers.forEach(function (layer) {
+      var height = 0;
+      var x = 0;
+      layer.forEach(function (id) {
+        var text = lines(blocks[id]);
+        var w = Math.max.apply(null, text.map(function (line) { return line.length; })) * CHAR + 2 * PAD;
+        var h = text.length * LINE + 2 * PAD;
+        boxes[id] = { x: x, y: y, w: w, h: h, lines: text };
+        x += w + NODE_GAP;
+        height = Math.max(height, h);
+      });
+      width = Math.max(width, x - NODE_GAP);
+      y += height + RANK_GAP;
+    });
+    // Pull nodes toward their predecessors without reordering them
+    layers.forEach(function (layer) {
+      var min = 0;
+      layer.forEach(function (id) {
+        var box = boxes[id];
+        var ps = preds[id];
+        var want = ps.length
+          ? ps.reduce(function (sum, p) { return sum + boxes[p].x + boxes[p].w / 2; }, 0) / ps.length - box.w / 2
+          : box.x;
+        box.x = Math.max(min, want);
+        min = box.x + box.w + NODE_GAP;
+        width = Math.max(width, min - NODE_GAP);
+      });
+    });
+    return { blocks: blocks, boxes: boxes, back: back, rank: rank, width: width, height: y - RANK_GAP };
+  }
+
+  function textLine(parent, x, y, line, cls) {
+    var text = el("text", { x: x, y: y, "class": cls || "" }, parent);
+    var pattern = /[A-Za-z_][\w.]*\(%\d+\)|%\d+/g;
+    var last = 0;
+    var match;
+    while ((match = pattern.exec(line))) {
+      if (match.index > last) {
+        text.appendChild(document.createTextNode(line.slice(last, match.index)));
+      }
+      var id = match[0].replace(/.*%(\d+)\)?$/, "$1");
+      var span = el("tspan", { "class": "id", "data-id": id }, text);
+      span.textContent = match[0];
+      last = match.index + match[0].length;
+    }
+    text.appendChild(document.createTextNode(line.slice(last)));
+  }
+
+  function render() {
+    var fn = data.functions[picker.value];
+    current = layout(fn);
+    while (scene.firstChild) {
+      scene.removeChild(scene.firstChild);
+    }
+    var edges = el("g", {}, scene);
+    var nodes = el("g", {}, scene);
+
+    fn.blocks.forEach(function (block) {
+      var box = current.boxes[block.id];
+      if (!box) {
+        return;
+      }
+      var g = el("g", {
+        "class": "node" + (block.reachable ? "" : " unreachable"),
+        "data-block": block.id,
+        transform: "translate(" + box.x + "," + box.y + ")"
+      }, nodes);
+      el("rect", { "class": "box", width: box.w, height: box.h, rx: 3 }, g);
+      el("rect", {
+        "class": "title",
+        x: 0.5, y: 0.5, width: box.w - 1, height: LINE + PAD,
+        fill: block.reachable ? block.color : "#f4f4f4"
+      }, g);
+      box.lines.forEach(function (line, i) {
+        var cls = i === 0 ? "name" : expanded[block.id] ? "" : "more";
+        textLine(g, PAD, PAD + (i + 1) * LINE - 4, line, cls);
+      });
+      g.addEventListener("click", function () {
+        expanded[block.id] = !expanded[block.id];
+        render();
+      });
+    });
+
+    fn.edges.forEach(function (edge) {
+      var from = current.boxes[edge.source];
+      var to = current.boxes[edge.target];
+      if (!from || !to) {
+        return;
+      }
+      var classes = ["edge", edge.kind];
+      if (edge.kind === "case" && edge["default"]) {
+        classes.push("default");
+      }
+      if (!current.blocks[edge.source].reachable) {
+        classes.push("unreachable");
+      }
+      var g = el("g", { "class": classes.join(" ") }, edges);
+      var d;
+      var label;
+      if (to.y > from.y) {
+        var x1 = from.x + from.w / 2;
+        var y1 = from.y + from.h;
+        var x2 = to.x + to.w / 2;
+        var y2 = to.y;
+        var bend = (y2 - y1) / 2;
+        d = "M" + x1 + "," + y1 + " C" + x1 + "," + (y1 + bend) + " " + x2 + "," + (y2 - bend) + " " + x2 + "," + y2;
+        label = [(x1 + x2) / 2, (y1 + y2) / 2];
+      } else {
+        // Edges going up leave and enter on the right side
+        var sx = from.x + from.w;
+        var sy = from.y + from.h / 2;
+        var tx = to.x + to.w;
+        var ty = to.y + to.h / 2;
+        var out = Math.max(sx, tx) + 40;
+        d = "M" + sx + "," + sy + " C" + out + "," + sy + " " + out + "," + ty + " " + tx + "," + ty;
+        label = [out - 10, (sy + ty) / 2];
+      }
+      el("path", { d: d, "marker-end": "url(#arrow)" }, g);
+      if (edge.label !== null) {
+        var t = el("text", { x: label[0] + 4, y: label[1] }, g);
+        t.textContent = edge.label;
+      }
+    });
+    apply();
+  }
+
+  function apply() {
+    scene.setAttribute("transform", "translate(" + view.x + "," + view.y + ") scale(" + view.k + ")");
+  }
+
+  function fit() {
+    var width = viewport.clientWidth;
+    var height = viewport.clientHeight;
+    view.k = Math.min(1.5, (width - 40) / (current.width + 80), (height - 40) / (current.height || 1));
+    view.x = (width - current.width * view.k) / 2;
+    view.y = 20;
+    apply();
+  }
+
+  // Pan and zoom
+  var drag = null;
+  var dragged = false;
+  viewport.addEventListener("mousedown", function (event) {
+    drag = { x: event.clientX - view.x, y: event.clientY - view.y };
+    dragged = false;
+    viewport.classList.add("dragging");
+  });
+  window.addEventListener("mousemove", function (event) {
+    if (drag) {
+      view.x = event.clientX - drag.x;
+      view.y = event.clientY - drag.y;
+      dragged = true;
+      apply();
+    }
+  });
+  window.addEventListener("mouseup", function () {
+    drag = null;
+    viewport.classList.remove("dragging");
+  });
+  // Don't toggle blocks at the end of a drag
+  viewport.addEventListener("click", function (event) {
+    if (dragged) {
+      event.stopPropagation();
+    }
+  }, true);
+  viewport.addEventListener("wheel", function (event) {
+    event.preventDefault();
+    var rect = svg.getBoundingClientRect();
+    var mx = event.clientX - rect.left;
+    var my = event.clientY - rect.top;
+    var factor = Math.exp(-event.deltaY * 0.0015);
+    view.x = mx - (mx - view.x) * factor;
+    view.y = my - (my - view.y) * factor;
+    view.k *= factor;
+    apply();
+  }, { passive: false });
+
+  // Highlight every use of the id under the mouse
+  scene.addEventListener("mouseover", function (event) {
+    var id = event.target.getAttribute && event.target.getAttribute("data-id");
+    if (!id) {
+      return;
+    }
+    scene.querySelectorAll('[data-id="' + id + '"]').forEach(function (span) {
+      span.classList.add("hl");
+    });
+    scene.querySelectorAll('[data-block="' + id + '"]').forEach(function (node) {
+      node.classList.add("hl");
+    });
+  });
+  scene.addEventListener("mouseout", function () {
+    scene.querySelectorAll(".hl").forEach(function (node) {
+      node.classList.remove("hl");
+    });
+  });
+
+  document.getElementById("fit").addEventListener("click", fit);
+  document.getElementById("expand").addEventListener("click", function () {
+    data.functions[picker.value].blocks.forEach(function (block) {
+      expanded[block.id] = true;
+    });
+    render();
+  });
+  document.getElementById("collapse").addEventListener("click", function () {
+    expanded = {};
+    render();
+  });
+
+  var entryFunctions = data.entry_points.map(function (entry) { return entry.function; });
+  var selected = -1;
+  data.functions.forEach(function (fn, i) {
+    var option = document.createElement("option");
+    option.value = i;
+    var entries = data.entry_points.filter(function (entry) { return entry.function === fn.id; });
+    option.textContent = (fn.name || "%" + fn.id) + entries.map(function (entry) {
+      return " [" + entry.execution_model + " " + entry.name + "]";
+    }).join("");
+    picker.appendChild(option);
+    if (selected < 0 && entryFunctions.indexOf(fn.id) >= 0) {
+      selected = i;
+    }
+  });
+  picker.addEventListener("change", function () {
+    render();
+    fit();
+  });
+  if (data.functions.length) {
+    picker.value = Math.max(selected, 0);
+    render();
+    fit();
+  }
+})();
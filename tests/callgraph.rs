@@ -0,0 +1,59 @@
+extern crate rspirv;
+extern crate rspirv_cfg;
+extern crate spirv_headers as spirv;
+
+use rspirv::binary::Assemble;
+use rspirv::dr::Builder;
+use rspirv_cfg::{CallGraph, SpirvModule};
+
+/// A module with `count` functions, where function `caller` calls `callee` for
+/// every `(caller, callee)` in `calls`. Returns the call graph and the function
+/// ids in module order.
+fn call_graph(count: usize, calls: &[(usize, usize)]) -> (CallGraph, Vec<u32>) {
+    let mut b = Builder::new();
+    b.capability(spirv::Capability::Shader);
+    b.memory_model(spirv::AddressingModel::Logical, spirv::MemoryModel::GLSL450);
+    let void = b.type_void();
+    let fn_ty = b.type_function(void, vec![]);
+    let functions: Vec<u32> = (0..count).map(|_| b.id()).collect();
+    for (index, &function) in functions.iter().enumerate() {
+        b.begin_function(void, Some(function), spirv::FunctionControl::NONE, fn_ty)
+            .unwrap();
+        b.begin_block(None).unwrap();
+        for &(_, callee) in calls.iter().filter(|&&(caller, _)| caller == index) {
+            b.function_call(void, None, functions[callee], []).unwrap();
+        }
+        b.ret().unwrap();
+        b.end_function().unwrap();
+    }
+    let module = SpirvModule::from_words(&b.module().assemble()).unwrap();
+    (CallGraph::new(&module), functions)
+}
+
+#[test]
+fn mutual_recursion() {
+    // 1 and 2 call each other, 0 calls into them from outside
+    let (graph, f) = call_graph(3, &[(0, 1), (1, 2), (2, 1)]);
+    assert_eq!(graph.recursion(), vec![vec![f[1], f[2]]]);
+
+    // Two separate cycles, 0 -> 3 -> 1 -> 0 and 2 -> 4 -> 2, reported in
+    // module order
+    let (graph, f) = call_graph(5, &[(0, 3), (3, 1), (1, 0), (2, 4), (4, 2), (1, 2)]);
+    assert_eq!(
+        graph.recursion(),
+        vec![vec![f[0], f[1], f[3]], vec![f[2], f[4]]]
+    );
+}
+
+#[test]
+fn self_recursion() {
+    let (graph, f) = call_graph(3, &[(0, 1), (1, 1), (1, 2)]);
+    assert_eq!(graph.recursion(), vec![vec![f[1]]]);
+}
+
+#[test]
+fn diamond_is_not_recursive() {
+    // 0 calls 1 and 2, both of which call 3
+    let (graph, _) = call_graph(4, &[(0, 1), (0, 2), (1, 3), (2, 3)]);
+    assert_eq!(graph.recursion(), Vec::<Vec<u32>>::new());
+}
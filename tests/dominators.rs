@@ -0,0 +1,233 @@
+extern crate rspirv;
+extern crate rspirv_cfg;
+extern crate spirv_headers as spirv;
+
+use rspirv::binary::Assemble;
+use rspirv::dr::{Builder, InsertPoint, Instruction, Operand};
+use rspirv_cfg::{DominatorTree, PetSpirv, SpirvModule};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A module with a single function of `count` blocks, `terminate` ends block
+/// `index` given the ids of all blocks and a boolean to branch on. Returns the
+/// module and the block ids in layout order.
+fn function(
+    count: usize,
+    terminate: impl Fn(&mut Builder, usize, &[u32], u32),
+) -> (SpirvModule, Vec<u32>) {
+    let mut b = Builder::new();
+    b.capability(spirv::Capability::Shader);
+    b.memory_model(spirv::AddressingModel::Logical, spirv::MemoryModel::GLSL450);
+    let void = b.type_void();
+    let boolean = b.type_bool();
+    let condition = b.constant_true(boolean);
+    let fn_ty = b.type_function(void, vec![]);
+    b.begin_function(void, None, spirv::FunctionControl::NONE, fn_ty)
+        .unwrap();
+    let blocks: Vec<u32> = (0..count).map(|_| b.id()).collect();
+    for index in 0..count {
+        b.begin_block(Some(blocks[index])).unwrap();
+        terminate(&mut b, index, &blocks, condition);
+    }
+    b.end_function().unwrap();
+    let module = SpirvModule::from_words(&b.module().assemble()).unwrap();
+    (module, blocks)
+}
+
+/// `OpSelectionMerge` or `OpLoopMerge`, the builder's own end the block.
+fn merge(b: &mut Builder, merge: u32, continue_target: Option<u32>) {
+    let inst = match continue_target {
+        Some(continue_target) => Instruction::new(
+            spirv::Op::LoopMerge,
+            None,
+            None,
+            vec![
+                Operand::IdRef(merge),
+                Operand::IdRef(continue_target),
+                Operand::LoopControl(spirv::LoopControl::NONE),
+            ],
+        ),
+        None => Instruction::new(
+            spirv::Op::SelectionMerge,
+            None,
+            None,
+            vec![
+                Operand::IdRef(merge),
+                Operand::SelectionControl(spirv::SelectionControl::NONE),
+            ],
+        ),
+    };
+    b.insert_into_block(InsertPoint::End, inst).unwrap();
+}
+
+/// The immediate dominator of every block in the tree, by layout index.
+fn idoms(tree: &DominatorTree, blocks: &[u32]) -> BTreeMap<usize, Option<usize>> {
+    let index = |id| blocks.iter().position(|&block| block == id).unwrap();
+    tree.iter()
+        .map(|(id, idom)| (index(id), idom.map(index)))
+        .collect()
+}
+
+/// The dominance frontier of every block in the tree, by layout index.
+fn frontier(tree: &DominatorTree, blocks: &[u32]) -> BTreeMap<usize, BTreeSet<usize>> {
+    let index = |id| blocks.iter().position(|&block| block == id).unwrap();
+    tree.dominance_frontier()
+        .into_iter()
+        .map(|(id, frontier)| (index(id), frontier.into_iter().map(index).collect()))
+        .collect()
+}
+
+fn set(indices: &[usize]) -> BTreeSet<usize> {
+    indices.iter().cloned().collect()
+}
+
+/// 0 branches to 1 or 2, both of which branch to 3.
+#[test]
+fn diamond() {
+    let (module, blocks) = function(4, |b, index, blocks, condition| match index {
+        0 => {
+            merge(b, blocks[3], None);
+            b.branch_conditional(condition, blocks[1], blocks[2], vec![])
+                .unwrap();
+        }
+        1 | 2 => b.branch(blocks[3]).unwrap(),
+        _ => b.ret().unwrap(),
+    });
+    let pet = PetSpirv::new(&module, &module.module.functions[0]);
+
+    let dominators = pet.dominator_tree();
+    assert_eq!(
+        idoms(&dominators, &blocks),
+        vec![(0, None), (1, Some(0)), (2, Some(0)), (3, Some(0))]
+            .into_iter()
+            .collect()
+    );
+    assert_eq!(
+        frontier(&dominators, &blocks),
+        vec![(0, set(&[])), (1, set(&[3])), (2, set(&[3])), (3, set(&[]))]
+            .into_iter()
+            .collect()
+    );
+
+    let post_dominators = pet.post_dominator_tree();
+    assert_eq!(
+        idoms(&post_dominators, &blocks),
+        vec![(0, Some(3)), (1, Some(3)), (2, Some(3)), (3, None)]
+            .into_iter()
+            .collect()
+    );
+    assert_eq!(
+        frontier(&post_dominators, &blocks),
+        vec![(0, set(&[])), (1, set(&[0])), (2, set(&[0])), (3, set(&[]))]
+            .into_iter()
+            .collect()
+    );
+}
+
+/// 1 is a loop header with continue target 3 and merge block 4, the body 2
+/// either breaks to 4 or continues.
+#[test]
+fn loop_with_break() {
+    let (module, blocks) = function(5, |b, index, blocks, condition| match index {
+        0 => b.branch(blocks[1]).unwrap(),
+        1 => {
+            merge(b, blocks[4], Some(blocks[3]));
+            b.branch(blocks[2]).unwrap();
+        }
+        2 => b
+            .branch_conditional(condition, blocks[4], blocks[3], vec![])
+            .unwrap(),
+        3 => b.branch(blocks[1]).unwrap(),
+        _ => b.ret().unwrap(),
+    });
+    let pet = PetSpirv::new(&module, &module.module.functions[0]);
+
+    let dominators = pet.dominator_tree();
+    assert_eq!(
+        idoms(&dominators, &blocks),
+        vec![
+            (0, None),
+            (1, Some(0)),
+            (2, Some(1)),
+            (3, Some(2)),
+            (4, Some(2)),
+        ]
+        .into_iter()
+        .collect()
+    );
+    assert_eq!(
+        frontier(&dominators, &blocks),
+        vec![
+            (0, set(&[])),
+            (1, set(&[1])),
+            (2, set(&[1])),
+            (3, set(&[1])),
+            (4, set(&[])),
+        ]
+        .into_iter()
+        .collect()
+    );
+
+    let post_dominators = pet.post_dominator_tree();
+    assert_eq!(
+        idoms(&post_dominators, &blocks),
+        vec![
+            (0, Some(1)),
+            (1, Some(2)),
+            (2, Some(4)),
+            (3, Some(1)),
+            (4, None),
+        ]
+        .into_iter()
+        .collect()
+    );
+    assert_eq!(
+        frontier(&post_dominators, &blocks),
+        vec![
+            (0, set(&[])),
+            (1, set(&[2])),
+            (2, set(&[2])),
+            (3, set(&[2])),
+            (4, set(&[])),
+        ]
+        .into_iter()
+        .collect()
+    );
+}
+
+/// 0 branches to 2, nothing branches to 1 which branches to 2 as well.
+#[test]
+fn unreachable_block() {
+    let (module, blocks) = function(3, |b, index, blocks, _| match index {
+        0 | 1 => b.branch(blocks[2]).unwrap(),
+        _ => b.ret().unwrap(),
+    });
+    let pet = PetSpirv::new(&module, &module.module.functions[0]);
+
+    let dominators = pet.dominator_tree();
+    assert!(!dominators.contains(blocks[1]));
+    assert!(!dominators.dominates(blocks[1], blocks[2]));
+    assert_eq!(
+        idoms(&dominators, &blocks),
+        vec![(0, None), (2, Some(0))].into_iter().collect()
+    );
+    // The edge from the unreachable block doesn't make 2 a join point
+    assert_eq!(
+        frontier(&dominators, &blocks),
+        vec![(0, set(&[])), (2, set(&[]))].into_iter().collect()
+    );
+
+    // Every block reaches the return, so all of them are post-dominated by it
+    let post_dominators = pet.post_dominator_tree();
+    assert_eq!(
+        idoms(&post_dominators, &blocks),
+        vec![(0, Some(2)), (1, Some(2)), (2, None)]
+            .into_iter()
+            .collect()
+    );
+    assert_eq!(
+        frontier(&post_dominators, &blocks),
+        vec![(0, set(&[])), (1, set(&[])), (2, set(&[]))]
+            .into_iter()
+            .collect()
+    );
+}
@@ -0,0 +1,240 @@
+extern crate rspirv;
+extern crate rspirv_cfg;
+extern crate spirv_headers as spirv;
+
+use rspirv::binary::Assemble;
+use rspirv::dr::{Builder, Instruction, Module, Operand};
+use rspirv_cfg::{
+    write_spirv_cfg, CallGraph, CfgError, DotOptions, Source, SpirvModule, Terminator,
+};
+use std::io;
+
+const VERSION_1_0: u32 = 0x0001_0000;
+
+/// A module with a single function whose only block ends in `terminator`,
+/// preceded by `merge` if given.
+fn single_block(merge: Option<Instruction>, terminator: Instruction) -> Module {
+    let mut b = Builder::new();
+    b.capability(spirv::Capability::Shader);
+    b.memory_model(spirv::AddressingModel::Logical, spirv::MemoryModel::GLSL450);
+    let void = b.type_void();
+    let fn_ty = b.type_function(void, vec![]);
+    b.begin_function(void, None, spirv::FunctionControl::NONE, fn_ty)
+        .unwrap();
+    b.begin_block(None).unwrap();
+    b.ret().unwrap();
+    b.end_function().unwrap();
+    let mut module = b.module();
+    let instructions = &mut module.functions[0].blocks[0].instructions;
+    instructions.clear();
+    instructions.extend(merge);
+    instructions.push(terminator);
+    module
+}
+
+fn to_bytes(module: &Module, big_endian: bool) -> Vec<u8> {
+    module
+        .assemble()
+        .iter()
+        .flat_map(|word| {
+            if big_endian {
+                word.to_be_bytes()
+            } else {
+                word.to_le_bytes()
+            }
+            .to_vec()
+        })
+        .collect()
+}
+
+fn inst(opcode: spirv::Op, operands: Vec<Operand>) -> Instruction {
+    Instruction::new(opcode, None, None, operands)
+}
+
+#[test]
+fn io_error() {
+    match SpirvModule::try_load(&"does/not/exist.spv") {
+        Err(CfgError::Io(_)) => {}
+        other => panic!("{:?}", other.err()),
+    }
+}
+
+#[test]
+fn truncated_header() {
+    match SpirvModule::from_bytes(&[0; 8]) {
+        Err(CfgError::HeaderTruncated { len: 8 }) => {}
+        other => panic!("{:?}", other.err()),
+    }
+}
+
+#[test]
+fn bad_magic() {
+    match SpirvModule::from_words(&[0xdead_beef, VERSION_1_0, 0, 1, 0]) {
+        Err(CfgError::BadMagic(0xdead_beef)) => {}
+        other => panic!("{:?}", other.err()),
+    }
+}
+
+#[test]
+fn unsupported_version() {
+    match SpirvModule::from_words(&[spirv::MAGIC_NUMBER, 0x0002_0000, 0, 1, 0]) {
+        Err(CfgError::UnsupportedVersion { major: 2, minor: 0 }) => {}
+        other => panic!("{:?}", other.err()),
+    }
+}
+
+#[test]
+fn parse_error() {
+    // An OpCapability with a word count of zero right after the header
+    let words = [spirv::MAGIC_NUMBER, VERSION_1_0, 0, 1, 0, 17];
+    match SpirvModule::from_words(&words) {
+        Err(CfgError::Parse {
+            word_offset: Some(5),
+            ..
+        }) => {}
+        other => panic!("{:?}", other.err()),
+    }
+}
+
+#[test]
+fn trailing_bytes() {
+    let mut bytes = to_bytes(&single_block(None, inst(spirv::Op::Return, vec![])), false);
+    let words = bytes.len() / 4;
+    bytes.push(0);
+    match SpirvModule::from_bytes(&bytes) {
+        Err(CfgError::Parse {
+            word_offset: Some(offset),
+            ..
+        }) => assert_eq!(offset, words),
+        other => panic!("{:?}", other.err()),
+    }
+}
+
+#[test]
+fn big_endian() {
+    let module = single_block(None, inst(spirv::Op::Return, vec![]));
+    let dot = |bytes: &[u8]| {
+        let module = SpirvModule::from_bytes(bytes).unwrap();
+        let mut dot = Vec::new();
+        write_spirv_cfg(
+            &module,
+            &CallGraph::new(&module),
+            &DotOptions::default(),
+            &mut dot,
+        )
+        .unwrap();
+        String::from_utf8(dot).unwrap()
+    };
+    assert_eq!(
+        dot(&to_bytes(&module, true)),
+        dot(&to_bytes(&module, false))
+    );
+
+    let mut bytes = to_bytes(&module, true);
+    bytes.extend(&[0, 0]);
+    match SpirvModule::from_bytes(&bytes) {
+        Err(CfgError::Parse {
+            word_offset: Some(_),
+            ..
+        }) => {}
+        other => panic!("{:?}", other.err()),
+    }
+}
+
+#[test]
+fn malformed_debug_instruction() {
+    let mut module = single_block(None, inst(spirv::Op::Return, vec![]));
+    module
+        .debugs
+        .push(inst(spirv::Op::Name, vec![Operand::IdRef(1)]));
+    match SpirvModule::from_module(module) {
+        Err(CfgError::MalformedDebugInstruction(inst)) => {
+            assert_eq!(inst.class.opcode, spirv::Op::Name)
+        }
+        other => panic!("{:?}", other.err()),
+    }
+}
+
+#[test]
+fn malformed_sources_are_skipped() {
+    let mut module = single_block(None, inst(spirv::Op::Return, vec![]));
+    let text = |text: &str| Operand::LiteralString(text.to_string());
+    let glsl = |rest: Vec<Operand>| {
+        let mut operands = vec![
+            Operand::SourceLanguage(spirv::SourceLanguage::GLSL),
+            Operand::LiteralInt32(450),
+        ];
+        operands.extend(rest);
+        inst(spirv::Op::Source, operands)
+    };
+    module.debugs.extend(vec![
+        // Continues nothing
+        inst(spirv::Op::SourceContinued, vec![text("a")]),
+        inst(spirv::Op::Source, vec![Operand::IdRef(1)]),
+        // Continues the malformed source
+        inst(spirv::Op::SourceContinued, vec![text("b")]),
+        glsl(vec![Operand::IdRef(1), text("c")]),
+        inst(spirv::Op::SourceContinued, vec![text("d")]),
+        inst(spirv::Op::SourceContinued, vec![Operand::IdRef(1)]),
+        glsl(vec![text("e")]),
+        glsl(vec![Operand::IdRef(2)]),
+        // The last source has no text to continue
+        inst(spirv::Op::SourceContinued, vec![text("f")]),
+    ]);
+    let module = SpirvModule::from_module(module).unwrap();
+    let source = |file, text: Option<&str>| Source {
+        language: spirv::SourceLanguage::GLSL,
+        version: 450,
+        file,
+        text: text.map(str::to_string),
+    };
+    assert_eq!(
+        module.source_map.sources,
+        vec![source(Some(1), Some("cd")), source(Some(2), None)]
+    );
+}
+
+#[test]
+fn malformed_terminators_are_missing() {
+    let literal = Operand::LiteralInt32(7);
+    let modules = vec![
+        single_block(None, inst(spirv::Op::Branch, vec![literal.clone()])),
+        single_block(None, inst(spirv::Op::Branch, vec![])),
+        single_block(
+            None,
+            inst(
+                spirv::Op::BranchConditional,
+                vec![Operand::IdRef(1), Operand::IdRef(2)],
+            ),
+        ),
+        single_block(None, inst(spirv::Op::ReturnValue, vec![literal.clone()])),
+        // A case literal without a target
+        single_block(
+            None,
+            inst(
+                spirv::Op::Switch,
+                vec![Operand::IdRef(1), Operand::IdRef(2), literal.clone()],
+            ),
+        ),
+        single_block(
+            Some(inst(spirv::Op::SelectionMerge, vec![literal.clone()])),
+            inst(spirv::Op::Branch, vec![Operand::IdRef(2)]),
+        ),
+        single_block(
+            Some(inst(spirv::Op::LoopMerge, vec![Operand::IdRef(2)])),
+            inst(spirv::Op::Branch, vec![Operand::IdRef(2)]),
+        ),
+    ];
+    for module in modules {
+        let module = SpirvModule::from_module(module).unwrap();
+        let block = &module.module.functions[0].blocks[0];
+        assert_eq!(Terminator::from_basic_block(block), Terminator::Missing);
+        write_spirv_cfg(
+            &module,
+            &CallGraph::new(&module),
+            &DotOptions::default(),
+            &mut io::sink(),
+        )
+        .unwrap();
+    }
+}
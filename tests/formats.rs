@@ -0,0 +1,73 @@
+extern crate rspirv;
+extern crate spirv_headers as spirv;
+
+use rspirv::binary::Assemble;
+use rspirv::dr::Builder;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+/// Writes a module with a single empty function to `path`.
+fn write_module(path: &Path) {
+    let mut b = Builder::new();
+    b.capability(spirv::Capability::Shader);
+    b.memory_model(spirv::AddressingModel::Logical, spirv::MemoryModel::GLSL450);
+    let void = b.type_void();
+    let fn_ty = b.type_function(void, vec![]);
+    b.begin_function(void, None, spirv::FunctionControl::NONE, fn_ty)
+        .unwrap();
+    b.begin_block(None).unwrap();
+    b.ret().unwrap();
+    b.end_function().unwrap();
+    let bytes: Vec<u8> = b
+        .module()
+        .assemble()
+        .iter()
+        .flat_map(|word| word.to_le_bytes().to_vec())
+        .collect();
+    fs::write(path, bytes).unwrap();
+}
+
+fn run(input: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rspirv-cfg"))
+        .arg("--file")
+        .arg(input)
+        .args(["--output", "-"])
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn dot_only_flags_are_rejected_with_other_formats() {
+    let input = std::env::temp_dir().join(format!("rspirv-cfg-formats-{}.spv", std::process::id()));
+    write_module(&input);
+    let flags: [&[&str]; 4] = [
+        &["--dominators", "overlay"],
+        &["--loops"],
+        &["--call-graph"],
+        &["--call-edges"],
+    ];
+    let mut failures = Vec::new();
+    for format in &["svg", "json", "mermaid", "html", "text"] {
+        for flag in &flags {
+            let mut args = vec!["--format", format];
+            args.extend_from_slice(flag);
+            let output = run(&input, &args);
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if output.status.success() || !stderr.contains(flag[0]) {
+                failures.push(args.join(" "));
+            }
+        }
+        if !run(&input, &["--format", format]).status.success() {
+            failures.push(format.to_string());
+        }
+    }
+    for flag in &flags {
+        if !run(&input, flag).status.success() {
+            failures.push(flag.join(" "));
+        }
+    }
+    fs::remove_file(&input).unwrap();
+    assert!(failures.is_empty(), "{:?}", failures);
+}
@@ -0,0 +1,224 @@
+extern crate rspirv;
+extern crate rspirv_cfg;
+extern crate serde_json;
+extern crate spirv_headers as spirv;
+
+use rspirv::binary::Assemble;
+use rspirv::dr::{Builder, InsertPoint, Instruction, Operand};
+use rspirv_cfg::{write_json, CallGraph, FunctionFilter, SpirvModule, JSON_SCHEMA_VERSION};
+use serde_json::{json, Value};
+
+/// Inserts a merge instruction, the builder's own end the block.
+fn merge(b: &mut Builder, opcode: spirv::Op, operands: Vec<Operand>) {
+    let inst = Instruction::new(opcode, None, None, operands);
+    b.insert_into_block(InsertPoint::End, inst).unwrap();
+}
+
+/// The JSON export of a fragment shader `main` with every kind of edge and
+/// an unreachable block, parsed. Returns the document, the id of `main` and
+/// its block ids in layout order.
+///
+/// Block 0 switches to 1 and 2, 1 falls through to the loop header 2 which
+/// either enters the loop body 3 or leaves for the merge block 5. 4 is the
+/// continue target and 6 is unreachable.
+fn document() -> (Value, u32, Vec<u32>) {
+    let mut b = Builder::new();
+    b.capability(spirv::Capability::Shader);
+    b.memory_model(spirv::AddressingModel::Logical, spirv::MemoryModel::GLSL450);
+    let void = b.type_void();
+    let boolean = b.type_bool();
+    let int = b.type_int(32, 0);
+    let condition = b.constant_true(boolean);
+    let selector = b.constant_u32(int, 7);
+    let fn_ty = b.type_function(void, vec![]);
+    let main = b
+        .begin_function(void, None, spirv::FunctionControl::NONE, fn_ty)
+        .unwrap();
+    let blocks: Vec<u32> = (0..7).map(|_| b.id()).collect();
+
+    b.begin_block(Some(blocks[0])).unwrap();
+    merge(
+        &mut b,
+        spirv::Op::SelectionMerge,
+        vec![
+            Operand::IdRef(blocks[2]),
+            Operand::SelectionControl(spirv::SelectionControl::NONE),
+        ],
+    );
+    b.switch(selector, blocks[1], [(1, blocks[1]), (7, blocks[2])])
+        .unwrap();
+    b.begin_block(Some(blocks[1])).unwrap();
+    b.branch(blocks[2]).unwrap();
+    b.begin_block(Some(blocks[2])).unwrap();
+    merge(
+        &mut b,
+        spirv::Op::LoopMerge,
+        vec![
+            Operand::IdRef(blocks[5]),
+            Operand::IdRef(blocks[4]),
+            Operand::LoopControl(spirv::LoopControl::NONE),
+        ],
+    );
+    b.branch_conditional(condition, blocks[3], blocks[5], [3, 1])
+        .unwrap();
+    b.begin_block(Some(blocks[3])).unwrap();
+    b.branch(blocks[4]).unwrap();
+    b.begin_block(Some(blocks[4])).unwrap();
+    b.branch(blocks[2]).unwrap();
+    b.begin_block(Some(blocks[5])).unwrap();
+    b.ret().unwrap();
+    b.begin_block(Some(blocks[6])).unwrap();
+    b.ret().unwrap();
+    b.end_function().unwrap();
+
+    b.entry_point(spirv::ExecutionModel::Fragment, main, "main", vec![]);
+    b.name(main, "main");
+    b.name(condition, "condition");
+    b.name(blocks[5], "say \"hi\"\n");
+
+    let module = SpirvModule::from_words(&b.module().assemble()).unwrap();
+    let mut json = Vec::new();
+    write_json(
+        &module,
+        &CallGraph::new(&module),
+        &FunctionFilter::default(),
+        &mut json,
+    )
+    .unwrap();
+    (serde_json::from_slice(&json).unwrap(), main, blocks)
+}
+
+fn keys(value: &Value) -> Vec<&str> {
+    let mut keys: Vec<&str> = value
+        .as_object()
+        .expect("object")
+        .keys()
+        .map(String::as_str)
+        .collect();
+    keys.sort();
+    keys
+}
+
+#[test]
+fn top_level() {
+    let (document, main, blocks) = document();
+    assert_eq!(
+        keys(&document),
+        vec!["entry_points", "functions", "names", "schema_version"]
+    );
+    assert_eq!(document["schema_version"], json!(2));
+    assert_eq!(document["schema_version"], json!(JSON_SCHEMA_VERSION));
+    assert_eq!(document["names"][main.to_string()], json!("main"));
+    assert_eq!(
+        document["names"][blocks[5].to_string()],
+        json!("say \"hi\"\n")
+    );
+    assert_eq!(
+        document["entry_points"],
+        json!([{ "function": main, "name": "main", "execution_model": "Fragment" }])
+    );
+
+    let functions = document["functions"].as_array().unwrap();
+    assert_eq!(functions.len(), 1);
+    let function = &functions[0];
+    assert_eq!(
+        keys(function),
+        vec!["blocks", "edges", "entry_block", "id", "name"]
+    );
+    assert_eq!(function["id"], json!(main));
+    assert_eq!(function["name"], json!("main"));
+    assert_eq!(function["entry_block"], json!(blocks[0]));
+}
+
+#[test]
+fn blocks() {
+    let (document, _, blocks) = document();
+    let json_blocks = document["functions"][0]["blocks"].as_array().unwrap();
+    let ids: Vec<Value> = json_blocks
+        .iter()
+        .map(|block| block["id"].clone())
+        .collect();
+    assert_eq!(Value::Array(ids), json!(blocks));
+    for (index, block) in json_blocks.iter().enumerate() {
+        assert_eq!(
+            keys(block),
+            vec!["color", "id", "instructions", "name", "reachable"]
+        );
+        assert_eq!(block["reachable"], json!(index != 6), "{}", block);
+        for inst in block["instructions"].as_array().unwrap() {
+            assert_eq!(
+                keys(inst),
+                vec!["opcode", "operands", "result_id", "result_type", "text"]
+            );
+            for operand in inst["operands"].as_array().unwrap() {
+                assert_eq!(keys(operand), vec!["kind", "value"]);
+            }
+        }
+    }
+    assert_eq!(json_blocks[0]["name"], Value::Null);
+    assert_eq!(json_blocks[0]["color"], json!("gray"));
+    assert_eq!(json_blocks[5]["color"], json!("palegreen"));
+    assert_eq!(json_blocks[5]["name"], json!("say \"hi\"\n"));
+
+    let switch = &json_blocks[0]["instructions"][1];
+    assert_eq!(switch["opcode"], json!("Switch"));
+    assert_eq!(switch["result_type"], Value::Null);
+    assert_eq!(switch["result_id"], Value::Null);
+    let kinds: Vec<&Value> = switch["operands"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|operand| &operand["kind"])
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            "IdRef",
+            "IdRef",
+            "LiteralInt32",
+            "IdRef",
+            "LiteralInt32",
+            "IdRef"
+        ]
+    );
+    assert_eq!(
+        switch["operands"][4],
+        json!({ "kind": "LiteralInt32", "value": 7 })
+    );
+    assert!(switch["text"].as_str().unwrap().starts_with("OpSwitch"));
+
+    let loop_merge = &json_blocks[2]["instructions"][0];
+    assert_eq!(loop_merge["opcode"], json!("LoopMerge"));
+    assert_eq!(
+        loop_merge["operands"][2],
+        json!({ "kind": "LoopControl", "value": "NONE" })
+    );
+}
+
+#[test]
+fn edges() {
+    let (document, _, b) = document();
+    let edges = document["functions"][0]["edges"].as_array().unwrap();
+    let condition = &document["functions"][0]["blocks"][2]["instructions"][1]["operands"][0];
+    let label = |branch: &str, weight: u32| {
+        format!(
+            "{}: condition(%{}) (weight {})",
+            branch, condition["value"], weight
+        )
+    };
+    assert_eq!(
+        edges,
+        &vec![
+            json!({ "source": b[0], "target": b[2], "kind": "selection_merge", "label": null }),
+            json!({ "source": b[0], "target": b[1], "kind": "case", "values": ["1"], "default": true, "label": "case 1, default" }),
+            json!({ "source": b[0], "target": b[2], "kind": "case", "values": ["7"], "default": false, "label": "case 7" }),
+            json!({ "source": b[1], "target": b[2], "kind": "branch", "label": null }),
+            json!({ "source": b[2], "target": b[5], "kind": "loop_merge", "label": "merge" }),
+            json!({ "source": b[2], "target": b[4], "kind": "continue", "label": "continue" }),
+            json!({ "source": b[2], "target": b[3], "kind": "true", "weight": 3, "label": label("true", 3) }),
+            json!({ "source": b[2], "target": b[5], "kind": "false", "weight": 1, "label": label("false", 1) }),
+            json!({ "source": b[3], "target": b[4], "kind": "branch", "label": null }),
+            json!({ "source": b[4], "target": b[2], "kind": "branch", "label": null }),
+        ]
+    );
+}
@@ -0,0 +1,89 @@
+extern crate rspirv;
+extern crate rspirv_cfg;
+extern crate spirv_headers as spirv;
+
+use rspirv::binary::Assemble;
+use rspirv::dr::Builder;
+use rspirv_cfg::{
+    write_spirv_cfg, write_text, CallGraph, DotOptions, PetSpirv, SpirvModule, TextOptions,
+};
+use std::io;
+
+const BLOCKS: usize = 100_000;
+
+/// A function with a chain of `BLOCKS` blocks where every third block can also
+/// skip the next one, deep enough to overflow any recursive walk.
+fn long_chain() -> Vec<u32> {
+    let mut b = Builder::new();
+    b.capability(spirv::Capability::Shader);
+    b.memory_model(spirv::AddressingModel::Logical, spirv::MemoryModel::GLSL450);
+    let void = b.type_void();
+    let boolean = b.type_bool();
+    let condition = b.constant_true(boolean);
+    let fn_ty = b.type_function(void, vec![]);
+    b.begin_function(void, None, spirv::FunctionControl::NONE, fn_ty)
+        .unwrap();
+    let ids: Vec<u32> = (0..BLOCKS).map(|_| b.id()).collect();
+    let last = ids[BLOCKS - 1];
+    for (i, &id) in ids.iter().enumerate() {
+        b.begin_block(Some(id)).unwrap();
+        if id == last {
+            b.ret().unwrap();
+        } else if i % 3 == 0 {
+            b.branch_conditional(condition, ids[i + 1], ids[i + 2], vec![])
+                .unwrap();
+        } else {
+            b.branch(ids[i + 1]).unwrap();
+        }
+    }
+    b.end_function().unwrap();
+    b.module().assemble()
+}
+
+#[test]
+fn traversals_of_100k_blocks() {
+    let module = SpirvModule::from_words(&long_chain()).unwrap();
+    let pet = PetSpirv::new(&module, &module.module.functions[0]);
+    let entry = pet.entry_block().unwrap();
+    let last = *pet.block_map.keys().last().unwrap();
+
+    let preorder: Vec<u32> = pet.preorder().collect();
+    assert_eq!(preorder.len(), BLOCKS);
+    assert_eq!(preorder[0], entry);
+    assert_eq!(preorder[BLOCKS - 1], last);
+
+    let postorder: Vec<u32> = pet.postorder().collect();
+    assert_eq!(postorder.len(), BLOCKS);
+    assert_eq!(postorder[0], last);
+    assert_eq!(postorder[BLOCKS - 1], entry);
+
+    let rpo = pet.reverse_postorder();
+    assert_eq!(rpo[0], entry);
+    assert!(rpo.windows(2).all(|pair| pair[0] < pair[1]));
+
+    let dominators = pet.dominator_tree();
+    assert_eq!(dominators.immediate_dominator(last), Some(postorder[1]));
+    assert!(pet.loop_info().loops.is_empty());
+}
+
+#[test]
+fn dot_output_of_100k_blocks() {
+    let module = SpirvModule::from_words(&long_chain()).unwrap();
+    let options = DotOptions {
+        loop_clusters: true,
+        ..DotOptions::default()
+    };
+    write_spirv_cfg(&module, &CallGraph::new(&module), &options, &mut io::sink()).unwrap();
+}
+
+#[test]
+fn text_output_of_100k_blocks() {
+    let module = SpirvModule::from_words(&long_chain()).unwrap();
+    write_text(
+        &module,
+        &CallGraph::new(&module),
+        &TextOptions::default(),
+        &mut io::sink(),
+    )
+    .unwrap();
+}
@@ -0,0 +1,90 @@
+extern crate rspirv_cfg;
+
+use rspirv_cfg::{layer_crossings, layout, Layout};
+
+#[test]
+fn crossings_between_two_layers() {
+    let segments = [(0, 0), (0, 1), (1, 0), (1, 2), (2, 1)];
+    // (0, 1) crosses (1, 0), (1, 2) crosses (2, 1)
+    assert_eq!(layer_crossings(&segments), 2);
+    // The order they are given in doesn't matter
+    let mut reversed = segments;
+    reversed.reverse();
+    assert_eq!(layer_crossings(&reversed), 2);
+
+    // Every pair of a complete reversal crosses
+    assert_eq!(layer_crossings(&[(0, 3), (1, 2), (2, 1), (3, 0)]), 6);
+    // Edges sharing an end or running parallel don't
+    assert_eq!(
+        layer_crossings(&[(0, 0), (0, 1), (0, 2), (1, 2), (2, 2)]),
+        0
+    );
+    assert_eq!(layer_crossings(&[(0, 1), (1, 2), (2, 3)]), 0);
+    assert_eq!(layer_crossings(&[]), 0);
+}
+
+/// The `(width, height)` of every node and the edges between them.
+type Graph = (Vec<(f64, f64)>, Vec<(usize, usize)>);
+
+/// A graph with `n` nodes of varying sizes and pseudo random edges, cycles
+/// and self loops included.
+fn graph(n: usize, edge_count: usize, seed: u64) -> Graph {
+    let mut state = seed;
+    let mut next = |bound: usize| {
+        state = state
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        (state >> 33) as usize % bound
+    };
+    let sizes = (0..n)
+        .map(|_| (40.0 + next(200) as f64, 20.0 + next(100) as f64))
+        .collect();
+    let edges = (0..edge_count).map(|_| (next(n), next(n))).collect();
+    (sizes, edges)
+}
+
+fn assert_no_overlap(sizes: &[(f64, f64)], layout: &Layout) {
+    assert_eq!(layout.nodes.len(), sizes.len());
+    for (a, (&(x, y), &(width, height))) in layout.nodes.iter().zip(sizes).enumerate() {
+        assert!(x >= 0.0 && y >= 0.0, "node {} at {:?}", a, (x, y));
+        assert!(x + width <= layout.width + 1e-6, "node {} too far right", a);
+        assert!(
+            y + height <= layout.height + 1e-6,
+            "node {} too far down",
+            a
+        );
+        for (b, (&(u, v), &(other_width, other_height))) in
+            layout.nodes.iter().zip(sizes).enumerate().skip(a + 1)
+        {
+            let apart = x + width <= u + 1e-6
+                || u + other_width <= x + 1e-6
+                || y + height <= v + 1e-6
+                || v + other_height <= y + 1e-6;
+            assert!(
+                apart,
+                "nodes {} at {:?} and {} at {:?} overlap",
+                a,
+                (x, y),
+                b,
+                (u, v)
+            );
+        }
+    }
+}
+
+#[test]
+fn nodes_never_overlap() {
+    for seed in 0..50 {
+        for &(n, edge_count) in &[(1, 0), (2, 1), (8, 12), (20, 30), (40, 45)] {
+            let (sizes, edges) = graph(n, edge_count, seed);
+            let layout = layout(&sizes, &edges);
+            assert_eq!(layout.edges.len(), edges.len());
+            assert_no_overlap(&sizes, &layout);
+        }
+    }
+
+    // One wide rank of unconnected nodes and a long chain next to it
+    let sizes = vec![(100.0, 30.0); 12];
+    let edges: Vec<(usize, usize)> = (6..11).map(|node| (node, node + 1)).collect();
+    assert_no_overlap(&sizes, &layout(&sizes, &edges));
+}
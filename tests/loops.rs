@@ -0,0 +1,220 @@
+extern crate rspirv;
+extern crate rspirv_cfg;
+extern crate spirv_headers as spirv;
+
+use rspirv::binary::Assemble;
+use rspirv::dr::{Builder, InsertPoint, Instruction, Operand};
+use rspirv_cfg::{write_spirv_cfg, CallGraph, DotOptions, PetSpirv, SpirvModule};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A module with a single function of `count` blocks, `terminate` ends block
+/// `index` given the ids of all blocks and a boolean to branch on. Returns the
+/// module and the block ids in layout order.
+fn function(
+    count: usize,
+    terminate: impl Fn(&mut Builder, usize, &[u32], u32),
+) -> (SpirvModule, Vec<u32>) {
+    let mut b = Builder::new();
+    b.capability(spirv::Capability::Shader);
+    b.memory_model(spirv::AddressingModel::Logical, spirv::MemoryModel::GLSL450);
+    let void = b.type_void();
+    let boolean = b.type_bool();
+    let condition = b.constant_true(boolean);
+    let fn_ty = b.type_function(void, vec![]);
+    b.begin_function(void, None, spirv::FunctionControl::NONE, fn_ty)
+        .unwrap();
+    let blocks: Vec<u32> = (0..count).map(|_| b.id()).collect();
+    for index in 0..count {
+        b.begin_block(Some(blocks[index])).unwrap();
+        terminate(&mut b, index, &blocks, condition);
+    }
+    b.end_function().unwrap();
+    let module = SpirvModule::from_words(&b.module().assemble()).unwrap();
+    (module, blocks)
+}
+
+/// `OpLoopMerge`, the builder's own ends the block.
+fn loop_merge(b: &mut Builder, merge: u32, continue_target: u32) {
+    let inst = Instruction::new(
+        spirv::Op::LoopMerge,
+        None,
+        None,
+        vec![
+            Operand::IdRef(merge),
+            Operand::IdRef(continue_target),
+            Operand::LoopControl(spirv::LoopControl::NONE),
+        ],
+    );
+    b.insert_into_block(InsertPoint::End, inst).unwrap();
+}
+
+fn set(blocks: &[u32], indices: &[usize]) -> BTreeSet<u32> {
+    indices.iter().map(|&index| blocks[index]).collect()
+}
+
+/// The blocks drawn directly in each loop cluster of the DOT output, keyed by
+/// the header of the cluster and the header of the cluster around it. Blocks
+/// outside of any loop are keyed by `(None, None)`.
+fn clusters(module: &SpirvModule) -> BTreeMap<(Option<u32>, Option<u32>), BTreeSet<u32>> {
+    let options = DotOptions {
+        loop_clusters: true,
+        ..Default::default()
+    };
+    let mut dot = Vec::new();
+    write_spirv_cfg(module, &CallGraph::new(module), &options, &mut dot).unwrap();
+    let dot = String::from_utf8(dot).unwrap();
+
+    let mut clusters: BTreeMap<_, BTreeSet<u32>> = BTreeMap::new();
+    let mut stack: Vec<u32> = Vec::new();
+    for line in dot.lines() {
+        if let Some(header) = line.strip_prefix("subgraph cluster_loop_") {
+            let header = header.trim_end_matches(" {").parse().unwrap();
+            clusters
+                .entry((Some(header), stack.last().cloned()))
+                .or_default();
+            stack.push(header);
+        } else if line == "}" {
+            stack.pop();
+        } else if let Some(node) = line.strip_prefix("  ") {
+            if let Some((id, _)) = node.split_once(" [shape=none") {
+                let parent = stack.len().checked_sub(2).map(|index| stack[index]);
+                clusters
+                    .entry((stack.last().cloned(), parent))
+                    .or_default()
+                    .insert(id.parse().unwrap());
+            }
+        }
+    }
+    clusters
+}
+
+/// The outer loop 1 continues at 5 and merges at 6, the inner loop 2 continues
+/// at 3 and merges at 4.
+#[test]
+fn nested_loops() {
+    let (module, blocks) = function(7, |b, index, blocks, condition| match index {
+        0 => b.branch(blocks[1]).unwrap(),
+        1 => {
+            loop_merge(b, blocks[6], blocks[5]);
+            b.branch(blocks[2]).unwrap();
+        }
+        2 => {
+            loop_merge(b, blocks[4], blocks[3]);
+            b.branch(blocks[3]).unwrap();
+        }
+        3 => b
+            .branch_conditional(condition, blocks[2], blocks[4], vec![])
+            .unwrap(),
+        4 => b.branch(blocks[5]).unwrap(),
+        5 => b
+            .branch_conditional(condition, blocks[1], blocks[6], vec![])
+            .unwrap(),
+        _ => b.ret().unwrap(),
+    });
+    let pet = PetSpirv::new(&module, &module.module.functions[0]);
+    let loops = pet.loop_info();
+    assert_eq!(loops.loops.len(), 2);
+    let (outer, inner) = (&loops.loops[0], &loops.loops[1]);
+
+    assert_eq!(outer.header, blocks[1]);
+    assert_eq!(outer.latches, vec![blocks[5]]);
+    assert_eq!(outer.body, set(&blocks, &[1, 2, 3, 4, 5]));
+    assert_eq!(outer.exits, set(&blocks, &[6]));
+    assert_eq!((outer.depth, outer.parent), (1, None));
+    assert_eq!(outer.children, vec![1]);
+
+    assert_eq!(inner.header, blocks[2]);
+    assert_eq!(inner.latches, vec![blocks[3]]);
+    assert_eq!(inner.body, set(&blocks, &[2, 3]));
+    assert_eq!(inner.exits, set(&blocks, &[4]));
+    assert_eq!((inner.depth, inner.parent), (2, Some(0)));
+
+    let depths: Vec<usize> = blocks.iter().map(|&id| loops.loop_depth(id)).collect();
+    assert_eq!(depths, vec![0, 1, 2, 2, 1, 1, 0]);
+    assert!(loops.irreducible_edges.is_empty());
+    assert!(loops.mismatches().is_empty());
+
+    assert_eq!(
+        clusters(&module),
+        vec![
+            ((None, None), set(&blocks, &[0, 6])),
+            ((Some(blocks[1]), None), set(&blocks, &[1, 4, 5])),
+            ((Some(blocks[2]), Some(blocks[1])), set(&blocks, &[2, 3])),
+        ]
+        .into_iter()
+        .collect()
+    );
+}
+
+/// A single block loop: 1 is its own continue target and merges at 2.
+#[test]
+fn continue_target_is_header() {
+    let (module, blocks) = function(3, |b, index, blocks, condition| match index {
+        0 => b.branch(blocks[1]).unwrap(),
+        1 => {
+            loop_merge(b, blocks[2], blocks[1]);
+            b.branch_conditional(condition, blocks[1], blocks[2], vec![])
+                .unwrap();
+        }
+        _ => b.ret().unwrap(),
+    });
+    let pet = PetSpirv::new(&module, &module.module.functions[0]);
+    let loops = pet.loop_info();
+    assert_eq!(loops.loops.len(), 1);
+    let l = &loops.loops[0];
+    assert_eq!(l.header, blocks[1]);
+    assert_eq!(l.latches, vec![blocks[1]]);
+    assert_eq!(l.body, set(&blocks, &[1]));
+    assert_eq!(l.exits, set(&blocks, &[2]));
+    assert!(loops.mismatches().is_empty());
+
+    assert_eq!(
+        clusters(&module),
+        vec![
+            ((None, None), set(&blocks, &[0, 2])),
+            ((Some(blocks[1]), None), set(&blocks, &[1])),
+        ]
+        .into_iter()
+        .collect()
+    );
+}
+
+/// Loop 1 continues at 4 and merges at 5. Block 2 breaks to the merge block,
+/// block 3 leaves the loop for block 6 which returns early.
+#[test]
+fn multi_exit_loop() {
+    let (module, blocks) = function(7, |b, index, blocks, condition| match index {
+        0 => b.branch(blocks[1]).unwrap(),
+        1 => {
+            loop_merge(b, blocks[5], blocks[4]);
+            b.branch(blocks[2]).unwrap();
+        }
+        2 => b
+            .branch_conditional(condition, blocks[5], blocks[3], vec![])
+            .unwrap(),
+        3 => b
+            .branch_conditional(condition, blocks[6], blocks[4], vec![])
+            .unwrap(),
+        4 => b.branch(blocks[1]).unwrap(),
+        _ => b.ret().unwrap(),
+    });
+    let pet = PetSpirv::new(&module, &module.module.functions[0]);
+    let loops = pet.loop_info();
+    assert_eq!(loops.loops.len(), 1);
+    let l = &loops.loops[0];
+    assert_eq!(l.header, blocks[1]);
+    assert_eq!(l.latches, vec![blocks[4]]);
+    assert_eq!(l.body, set(&blocks, &[1, 2, 3, 4]));
+    assert_eq!(l.exits, set(&blocks, &[5, 6]));
+    assert_eq!(loops.innermost_loop(blocks[6]), None);
+
+    assert_eq!(
+        clusters(&module),
+        vec![
+            ((None, None), set(&blocks, &[0, 5, 6])),
+            ((Some(blocks[1]), None), set(&blocks, &[1, 2, 3, 4])),
+        ]
+        .into_iter()
+        .collect()
+    );
+}
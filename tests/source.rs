@@ -0,0 +1,114 @@
+extern crate rspirv_cfg;
+extern crate spirv_headers as spirv;
+
+use rspirv_cfg::{write_text, CallGraph, SourceView, SpirvModule, TextOptions};
+
+fn op(opcode: spirv::Op, operands: &[u32]) -> Vec<u32> {
+    let mut words = vec![(operands.len() as u32 + 1) << 16 | opcode as u32];
+    words.extend_from_slice(operands);
+    words
+}
+
+/// The words of a nul terminated literal string.
+fn string(text: &str) -> Vec<u32> {
+    let mut bytes = text.as_bytes().to_vec();
+    bytes.resize(bytes.len() / 4 * 4 + 4, 0);
+    bytes
+        .chunks(4)
+        .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+        .collect()
+}
+
+/// A function compiled from the embedded `a.glsl`, assembled by hand. The
+/// `OpLine`s in front of `OpFunction` and `OpLabel` don't reach into the
+/// blocks, block 6 starts without a location and block 8 has none.
+fn module() -> Vec<u32> {
+    let mut source = vec![spirv::SourceLanguage::GLSL as u32, 450, 1];
+    source.extend(string("void main() {\n  int x = 1;\n"));
+    let mut file = vec![1];
+    file.extend(string("a.glsl"));
+    let instructions = vec![
+        op(spirv::Op::Capability, &[spirv::Capability::Shader as u32]),
+        op(spirv::Op::MemoryModel, &[0, 1]),
+        op(spirv::Op::String, &file),
+        op(spirv::Op::Source, &source),
+        op(spirv::Op::SourceContinued, &string("  x += 2;\n}\n")),
+        op(spirv::Op::TypeVoid, &[2]),
+        op(spirv::Op::TypeFunction, &[3, 2]),
+        op(spirv::Op::TypeInt, &[4, 32, 1]),
+        op(spirv::Op::Constant, &[4, 10, 1]),
+        op(spirv::Op::Line, &[1, 1, 1]),
+        op(spirv::Op::Function, &[2, 5, 0, 3]),
+        op(spirv::Op::Line, &[1, 4, 1]),
+        op(spirv::Op::Label, &[6]),
+        op(spirv::Op::IAdd, &[4, 11, 10, 10]),
+        op(spirv::Op::Line, &[1, 2, 3]),
+        op(spirv::Op::IAdd, &[4, 12, 10, 10]),
+        op(spirv::Op::IAdd, &[4, 13, 12, 10]),
+        op(spirv::Op::Line, &[1, 3, 3]),
+        op(spirv::Op::IAdd, &[4, 14, 13, 10]),
+        op(spirv::Op::Branch, &[8]),
+        op(spirv::Op::Label, &[8]),
+        op(spirv::Op::Return, &[]),
+        op(spirv::Op::FunctionEnd, &[]),
+    ];
+    let mut words = vec![spirv::MAGIC_NUMBER, 0x0001_0000, 0, 15, 0];
+    words.extend(instructions.into_iter().flatten());
+    words
+}
+
+/// The contents of the block boxes of the ASCII text output.
+fn listing(view: SourceView) -> Vec<String> {
+    let module = SpirvModule::from_words(&module()).unwrap();
+    let options = TextOptions {
+        ascii: true,
+        source_view: view,
+        ..TextOptions::default()
+    };
+    let mut text = Vec::new();
+    write_text(&module, &CallGraph::new(&module), &options, &mut text).unwrap();
+    String::from_utf8(text)
+        .unwrap()
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            if line.starts_with("| ") && line.ends_with(" |") {
+                Some(line[2..line.len() - 2].trim().to_string())
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn interleaved_source_lines() {
+    assert_eq!(
+        listing(SourceView::Interleave),
+        vec![
+            "%6",
+            "%11 = OpIAdd %4 %10 %10",
+            "// a.glsl:2: int x = 1;",
+            "%12 = OpIAdd %4 %10 %10",
+            "%13 = OpIAdd %4 %12 %10",
+            "// a.glsl:3: x += 2;",
+            "%14 = OpIAdd %4 %13 %10",
+            "OpBranch %8",
+            "%8",
+            "OpReturn",
+        ]
+    );
+    assert_eq!(
+        listing(SourceView::Disassembly),
+        vec![
+            "%6",
+            "%11 = OpIAdd %4 %10 %10",
+            "%12 = OpIAdd %4 %10 %10",
+            "%13 = OpIAdd %4 %12 %10",
+            "%14 = OpIAdd %4 %13 %10",
+            "OpBranch %8",
+            "%8",
+            "OpReturn",
+        ]
+    );
+}
@@ -0,0 +1,73 @@
+extern crate rspirv;
+extern crate rspirv_cfg;
+extern crate spirv_headers as spirv;
+
+use rspirv::binary::Assemble;
+use rspirv::dr::Builder;
+use rspirv_cfg::{export_spirv_cfg_per_function, CallGraph, DotOptions, SpirvModule};
+use std::collections::HashSet;
+use std::fs;
+use std::process::Command;
+
+/// A module with two functions named `foo`, preceded by one named after the
+/// de-duplicated file name of the second.
+fn clashing_names() -> Vec<u32> {
+    let mut b = Builder::new();
+    b.capability(spirv::Capability::Shader);
+    b.memory_model(spirv::AddressingModel::Logical, spirv::MemoryModel::GLSL450);
+    let void = b.type_void();
+    let fn_ty = b.type_function(void, vec![]);
+    let mut functions = Vec::new();
+    for _ in 0..3 {
+        let f = b
+            .begin_function(void, None, spirv::FunctionControl::NONE, fn_ty)
+            .unwrap();
+        b.begin_block(None).unwrap();
+        b.ret().unwrap();
+        b.end_function().unwrap();
+        functions.push(f);
+    }
+    b.name(functions[0], format!("foo_{}", functions[2]));
+    b.name(functions[1], "foo");
+    b.name(functions[2], "foo");
+    b.module().assemble()
+}
+
+#[test]
+fn file_names_are_unique() {
+    let module = SpirvModule::from_words(&clashing_names()).unwrap();
+    let dir = std::env::temp_dir().join(format!("rspirv-cfg-split-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let paths = export_spirv_cfg_per_function(
+        &module,
+        &CallGraph::new(&module),
+        &DotOptions::default(),
+        &dir,
+    )
+    .unwrap();
+    let unique: HashSet<_> = paths.iter().collect();
+    assert_eq!(unique.len(), 3, "{:?}", paths);
+    for path in &paths {
+        assert!(fs::read_to_string(path).unwrap().starts_with("digraph"));
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn split_to_stdout_is_rejected() {
+    let input = std::env::temp_dir().join(format!("rspirv-cfg-split-{}.spv", std::process::id()));
+    let bytes: Vec<u8> = clashing_names()
+        .iter()
+        .flat_map(|word| word.to_le_bytes().to_vec())
+        .collect();
+    fs::write(&input, bytes).unwrap();
+    let run = Command::new(env!("CARGO_BIN_EXE_rspirv-cfg"))
+        .arg("--file")
+        .arg(&input)
+        .args(["--split", "--output", "-"])
+        .output()
+        .unwrap();
+    fs::remove_file(&input).unwrap();
+    assert!(!run.status.success());
+    assert!(String::from_utf8_lossy(&run.stderr).contains("--split"));
+}
@@ -0,0 +1,74 @@
+extern crate rspirv;
+extern crate rspirv_cfg;
+extern crate spirv_headers as spirv;
+
+use rspirv::binary::Assemble;
+use rspirv::dr::Builder;
+use rspirv_cfg::{
+    write_json, write_spirv_cfg, CallGraph, DotOptions, PetSpirv, SpirvModule, Terminator,
+};
+
+const KILL: u32 = 1 << 16 | spirv::Op::Kill as u32;
+const TERMINATE_INVOCATION: u32 = 1 << 16 | 4416;
+
+/// A module with a function named `discard` ending in `OpKill` and one named
+/// `terminate` ending in `OpTerminateInvocation`, assembled by hand as rspirv
+/// doesn't know the opcode.
+fn discards() -> Vec<u32> {
+    let mut b = Builder::new();
+    b.capability(spirv::Capability::Shader);
+    b.extension("SPV_KHR_terminate_invocation");
+    b.memory_model(spirv::AddressingModel::Logical, spirv::MemoryModel::GLSL450);
+    let void = b.type_void();
+    let fn_ty = b.type_function(void, vec![]);
+    for name in &["discard", "terminate"] {
+        let f = b
+            .begin_function(void, None, spirv::FunctionControl::NONE, fn_ty)
+            .unwrap();
+        b.name(f, *name);
+        b.begin_block(None).unwrap();
+        b.kill().unwrap();
+        b.end_function().unwrap();
+    }
+    let mut words = b.module().assemble();
+    let last_kill = words.iter().rposition(|&word| word == KILL).unwrap();
+    words[last_kill] = TERMINATE_INVOCATION;
+    words
+}
+
+#[test]
+fn terminate_invocation_is_a_terminator() {
+    let module = SpirvModule::from_words(&discards()).unwrap();
+    let terminators: Vec<Terminator> = module
+        .module
+        .functions
+        .iter()
+        .map(|f| Terminator::from_basic_block(&f.blocks[0]))
+        .collect();
+    assert_eq!(
+        terminators,
+        vec![Terminator::Kill, Terminator::TerminateInvocation]
+    );
+    let pet = PetSpirv::new(&module, &module.module.functions[1]);
+    assert!(pet.validate().is_empty());
+}
+
+#[test]
+fn terminate_invocation_is_drawn_like_kill() {
+    let module = SpirvModule::from_words(&discards()).unwrap();
+    let graph = CallGraph::new(&module);
+    let mut dot = Vec::new();
+    write_spirv_cfg(&module, &graph, &DotOptions::default(), &mut dot).unwrap();
+    let dot = String::from_utf8(dot).unwrap();
+    assert!(dot.contains("OpTerminateInvocation"), "{}", dot);
+    assert_eq!(dot.matches("salmon").count(), 2, "{}", dot);
+
+    let mut json = Vec::new();
+    write_json(&module, &graph, &Default::default(), &mut json).unwrap();
+    let json = String::from_utf8(json).unwrap();
+    assert!(
+        json.contains("{ \"opcode\": \"TerminateInvocation\", \"result_type\": null, \"result_id\": null, \"operands\": [], \"text\": \"OpTerminateInvocation\" }"),
+        "{}",
+        json
+    );
+}
@@ -0,0 +1,444 @@
+extern crate rspirv;
+extern crate rspirv_cfg;
+extern crate spirv_headers as spirv;
+
+use rspirv::binary::Assemble;
+use rspirv::dr::{Builder, InsertPoint, Instruction, Operand};
+use rspirv_cfg::{PetSpirv, SpirvModule, Violation};
+use std::fs;
+use std::process::Command;
+
+/// Ids handed to the block builder: the blocks in layout order, a boolean and
+/// an integer to branch on and an id that is not a block.
+struct Ids {
+    blocks: Vec<u32>,
+    condition: u32,
+    selector: u32,
+    unknown: u32,
+}
+
+/// Assembles a module with a single function of `count` blocks, `terminate`
+/// ends block `index`.
+fn shader(count: usize, terminate: impl Fn(&mut Builder, usize, &Ids)) -> Vec<u32> {
+    let mut b = Builder::new();
+    b.capability(spirv::Capability::Shader);
+    b.memory_model(spirv::AddressingModel::Logical, spirv::MemoryModel::GLSL450);
+    let void = b.type_void();
+    let boolean = b.type_bool();
+    let int = b.type_int(32, 0);
+    let condition = b.constant_true(boolean);
+    let selector = b.constant_u32(int, 1);
+    let fn_ty = b.type_function(void, vec![]);
+    let main = b
+        .begin_function(void, None, spirv::FunctionControl::NONE, fn_ty)
+        .unwrap();
+    let ids = Ids {
+        blocks: (0..count).map(|_| b.id()).collect(),
+        condition,
+        selector,
+        unknown: b.id(),
+    };
+    for index in 0..count {
+        b.begin_block(Some(ids.blocks[index])).unwrap();
+        terminate(&mut b, index, &ids);
+    }
+    b.end_function().unwrap();
+    b.entry_point(spirv::ExecutionModel::Fragment, main, "main", vec![]);
+    b.module().assemble()
+}
+
+/// `OpSelectionMerge`, the builder's own ends the block.
+fn selection_merge(b: &mut Builder, merge: u32) {
+    let inst = Instruction::new(
+        spirv::Op::SelectionMerge,
+        None,
+        None,
+        vec![
+            Operand::IdRef(merge),
+            Operand::SelectionControl(spirv::SelectionControl::NONE),
+        ],
+    );
+    b.insert_into_block(InsertPoint::End, inst).unwrap();
+}
+
+/// `OpLoopMerge`, the builder's own ends the block.
+fn loop_merge(b: &mut Builder, merge: u32, continue_target: u32) {
+    let inst = Instruction::new(
+        spirv::Op::LoopMerge,
+        None,
+        None,
+        vec![
+            Operand::IdRef(merge),
+            Operand::IdRef(continue_target),
+            Operand::LoopControl(spirv::LoopControl::NONE),
+        ],
+    );
+    b.insert_into_block(InsertPoint::End, inst).unwrap();
+}
+
+fn violations(words: &[u32]) -> Vec<Violation> {
+    let module = SpirvModule::from_words(words).unwrap();
+    PetSpirv::new(&module, &module.module.functions[0]).validate()
+}
+
+fn assert_valid(words: &[u32]) {
+    assert_eq!(violations(words), Vec::new());
+}
+
+fn assert_violation(words: &[u32], matches: impl Fn(&Violation) -> bool) {
+    let violations = violations(words);
+    assert!(violations.iter().any(matches), "{:?}", violations);
+}
+
+fn if_else() -> Vec<u32> {
+    shader(4, |b, i, ids| {
+        let bl = &ids.blocks;
+        match i {
+            0 => {
+                selection_merge(b, bl[3]);
+                b.branch_conditional(ids.condition, bl[1], bl[2], vec![])
+                    .unwrap()
+            }
+            1 | 2 => b.branch(bl[3]).unwrap(),
+            _ => b.ret().unwrap(),
+        }
+    })
+}
+
+/// `do { if (x) break; } while (c);`, the loop merge is only reached from
+/// inside the selection and from the continue target.
+fn break_from_if_in_do_while() -> Vec<u32> {
+    shader(6, |b, i, ids| {
+        let bl = &ids.blocks;
+        match i {
+            0 => b.branch(bl[1]).unwrap(),
+            1 => {
+                loop_merge(b, bl[5], bl[4]);
+                b.branch(bl[2]).unwrap()
+            }
+            2 => {
+                selection_merge(b, bl[3]);
+                b.branch_conditional(ids.condition, bl[5], bl[3], vec![])
+                    .unwrap()
+            }
+            3 => b.branch(bl[4]).unwrap(),
+            4 => b
+                .branch_conditional(ids.condition, bl[1], bl[5], vec![])
+                .unwrap(),
+            _ => b.ret().unwrap(),
+        }
+    })
+}
+
+/// `while (true) { if (x) break; }` with the break in its own block.
+fn break_from_if_in_endless_loop() -> Vec<u32> {
+    shader(7, |b, i, ids| {
+        let bl = &ids.blocks;
+        match i {
+            0 => b.branch(bl[1]).unwrap(),
+            1 => {
+                loop_merge(b, bl[6], bl[5]);
+                b.branch(bl[2]).unwrap()
+            }
+            2 => {
+                selection_merge(b, bl[4]);
+                b.branch_conditional(ids.condition, bl[3], bl[4], vec![])
+                    .unwrap()
+            }
+            3 => b.branch(bl[6]).unwrap(),
+            4 => b.branch(bl[5]).unwrap(),
+            5 => b.branch(bl[1]).unwrap(),
+            _ => b.ret().unwrap(),
+        }
+    })
+}
+
+fn single_block_loop() -> Vec<u32> {
+    shader(3, |b, i, ids| {
+        let bl = &ids.blocks;
+        match i {
+            0 => b.branch(bl[1]).unwrap(),
+            1 => {
+                loop_merge(b, bl[2], bl[1]);
+                b.branch_conditional(ids.condition, bl[1], bl[2], vec![])
+                    .unwrap()
+            }
+            _ => b.ret().unwrap(),
+        }
+    })
+}
+
+/// A switch where case 1 falls through to case 2.
+fn switch_fallthrough() -> Vec<u32> {
+    shader(4, |b, i, ids| {
+        let bl = &ids.blocks;
+        match i {
+            0 => {
+                selection_merge(b, bl[3]);
+                b.switch(ids.selector, bl[3], vec![(1, bl[1]), (2, bl[2])])
+                    .unwrap()
+            }
+            1 => b.branch(bl[2]).unwrap(),
+            2 => b.branch(bl[3]).unwrap(),
+            _ => b.ret().unwrap(),
+        }
+    })
+}
+
+/// An inner selection that branches straight to the merge block of the outer
+/// selection.
+fn exit_to_outer_merge() -> Vec<u32> {
+    shader(5, |b, i, ids| {
+        let bl = &ids.blocks;
+        match i {
+            0 => {
+                selection_merge(b, bl[4]);
+                b.branch_conditional(ids.condition, bl[1], bl[4], vec![])
+                    .unwrap()
+            }
+            1 => {
+                selection_merge(b, bl[3]);
+                b.branch_conditional(ids.condition, bl[2], bl[3], vec![])
+                    .unwrap()
+            }
+            2 => b.branch(bl[4]).unwrap(),
+            3 => b.branch(bl[4]).unwrap(),
+            _ => b.ret().unwrap(),
+        }
+    })
+}
+
+#[test]
+fn valid_shapes() {
+    assert_valid(&if_else());
+    assert_valid(&break_from_if_in_do_while());
+    assert_valid(&break_from_if_in_endless_loop());
+    assert_valid(&single_block_loop());
+    assert_valid(&switch_fallthrough());
+}
+
+#[test]
+fn unknown_ids() {
+    let words = shader(1, |b, _, ids| b.branch(ids.unknown).unwrap());
+    assert_violation(&words, |v| {
+        matches!(v, Violation::UnknownBranchTarget { .. })
+    });
+
+    let words = shader(3, |b, i, ids| {
+        let bl = &ids.blocks;
+        match i {
+            0 => {
+                selection_merge(b, ids.unknown);
+                b.branch_conditional(ids.condition, bl[1], bl[2], vec![])
+                    .unwrap()
+            }
+            _ => b.ret().unwrap(),
+        }
+    });
+    assert_violation(&words, |v| matches!(v, Violation::UnknownMergeBlock { .. }));
+
+    let words = shader(4, |b, i, ids| {
+        let bl = &ids.blocks;
+        match i {
+            0 => b.branch(bl[1]).unwrap(),
+            1 => {
+                loop_merge(b, bl[3], ids.unknown);
+                b.branch(bl[2]).unwrap()
+            }
+            2 => b.branch(bl[3]).unwrap(),
+            _ => b.ret().unwrap(),
+        }
+    });
+    assert_violation(&words, |v| {
+        matches!(v, Violation::UnknownContinueTarget { .. })
+    });
+}
+
+#[test]
+fn merge_and_continue_declarations() {
+    // The merge block can also be reached around the header
+    let words = shader(5, |b, i, ids| {
+        let bl = &ids.blocks;
+        match i {
+            0 => b
+                .branch_conditional(ids.condition, bl[1], bl[2], vec![])
+                .unwrap(),
+            1 => {
+                selection_merge(b, bl[4]);
+                b.branch_conditional(ids.condition, bl[3], bl[4], vec![])
+                    .unwrap()
+            }
+            2 | 3 => b.branch(bl[4]).unwrap(),
+            _ => b.ret().unwrap(),
+        }
+    });
+    assert_violation(&words, |v| matches!(v, Violation::MergeNotDominated { .. }));
+
+    // The continue target can also be reached around the header
+    let words = shader(5, |b, i, ids| {
+        let bl = &ids.blocks;
+        match i {
+            0 => b
+                .branch_conditional(ids.condition, bl[1], bl[2], vec![])
+                .unwrap(),
+            1 => {
+                loop_merge(b, bl[4], bl[2]);
+                b.branch(bl[3]).unwrap()
+            }
+            2 => b.branch(bl[1]).unwrap(),
+            3 => b.branch(bl[4]).unwrap(),
+            _ => b.ret().unwrap(),
+        }
+    });
+    assert_violation(&words, |v| {
+        matches!(v, Violation::ContinueNotDominated { .. })
+    });
+
+    let words = shader(3, |b, i, ids| {
+        let bl = &ids.blocks;
+        match i {
+            0 => b.branch(bl[1]).unwrap(),
+            1 => {
+                loop_merge(b, bl[2], bl[2]);
+                b.branch(bl[2]).unwrap()
+            }
+            _ => b.ret().unwrap(),
+        }
+    });
+    assert_violation(&words, |v| matches!(v, Violation::MergeIsContinue { .. }));
+
+    let words = shader(4, |b, i, ids| {
+        let bl = &ids.blocks;
+        match i {
+            0 => {
+                selection_merge(b, bl[3]);
+                b.branch_conditional(ids.condition, bl[1], bl[3], vec![])
+                    .unwrap()
+            }
+            1 => {
+                selection_merge(b, bl[3]);
+                b.branch_conditional(ids.condition, bl[2], bl[3], vec![])
+                    .unwrap()
+            }
+            2 => b.branch(bl[3]).unwrap(),
+            _ => b.ret().unwrap(),
+        }
+    });
+    assert_violation(&words, |v| matches!(v, Violation::SharedMergeBlock { .. }));
+}
+
+#[test]
+fn back_edges() {
+    let words = shader(4, |b, i, ids| {
+        let bl = &ids.blocks;
+        match i {
+            0 => b.branch(bl[1]).unwrap(),
+            1 => b.branch(bl[2]).unwrap(),
+            2 => b
+                .branch_conditional(ids.condition, bl[1], bl[3], vec![])
+                .unwrap(),
+            _ => b.ret().unwrap(),
+        }
+    });
+    assert_violation(&words, |v| {
+        matches!(v, Violation::BackEdgeToNonHeader { .. })
+    });
+
+    // Both the body and the continue target branch back to the header
+    let words = shader(5, |b, i, ids| {
+        let bl = &ids.blocks;
+        match i {
+            0 => b.branch(bl[1]).unwrap(),
+            1 => {
+                loop_merge(b, bl[4], bl[3]);
+                b.branch(bl[2]).unwrap()
+            }
+            2 => b
+                .branch_conditional(ids.condition, bl[1], bl[3], vec![])
+                .unwrap(),
+            3 => b
+                .branch_conditional(ids.condition, bl[1], bl[4], vec![])
+                .unwrap(),
+            _ => b.ret().unwrap(),
+        }
+    });
+    assert_violation(&words, |v| matches!(v, Violation::MultipleBackEdges { .. }));
+    assert_violation(&words, |v| {
+        matches!(v, Violation::BackEdgeOutsideContinue { .. })
+    });
+}
+
+#[test]
+fn constructs() {
+    let words = shader(4, |b, i, ids| {
+        let bl = &ids.blocks;
+        match i {
+            0 => b
+                .branch_conditional(ids.condition, bl[1], bl[2], vec![])
+                .unwrap(),
+            1 | 2 => b.branch(bl[3]).unwrap(),
+            _ => b.ret().unwrap(),
+        }
+    });
+    assert_violation(&words, |v| matches!(v, Violation::MissingMerge { .. }));
+
+    // The continue target branches back into the loop body
+    let words = shader(5, |b, i, ids| {
+        let bl = &ids.blocks;
+        match i {
+            0 => b.branch(bl[1]).unwrap(),
+            1 => {
+                loop_merge(b, bl[4], bl[3]);
+                b.branch(bl[2]).unwrap()
+            }
+            2 => b.branch(bl[3]).unwrap(),
+            3 => b
+                .branch_conditional(ids.condition, bl[1], bl[2], vec![])
+                .unwrap(),
+            _ => b.ret().unwrap(),
+        }
+    });
+    let body = SpirvModule::from_words(&words).unwrap().module.functions[0].blocks[2]
+        .label
+        .as_ref()
+        .unwrap()
+        .result_id
+        .unwrap();
+    assert_violation(&words, |v| match v {
+        Violation::EntersConstruct { target, .. } => *target == body,
+        _ => false,
+    });
+
+    assert_violation(&exit_to_outer_merge(), |v| {
+        matches!(v, Violation::ExitsConstruct { .. })
+    });
+}
+
+/// Runs the binary with `--validate` on a module and returns whether it succeeded.
+fn validate_command(name: &str, words: &[u32]) -> bool {
+    let path = std::env::temp_dir().join(format!("rspirv-cfg-{}-{}", std::process::id(), name));
+    let input = path.with_extension("spv");
+    let output = path.with_extension("dot");
+    let bytes: Vec<u8> = words
+        .iter()
+        .flat_map(|word| word.to_le_bytes().to_vec())
+        .collect();
+    fs::write(&input, bytes).unwrap();
+    let run = Command::new(env!("CARGO_BIN_EXE_rspirv-cfg"))
+        .arg("--file")
+        .arg(&input)
+        .arg("--validate")
+        .arg("--output")
+        .arg(&output)
+        .output()
+        .unwrap();
+    fs::remove_file(&input).unwrap();
+    let _ = fs::remove_file(&output);
+    run.status.success()
+}
+
+#[test]
+fn validate_exit_status() {
+    assert!(validate_command("valid", &break_from_if_in_do_while()));
+    assert!(!validate_command("invalid", &exit_to_outer_merge()));
+}
@@ -0,0 +1,168 @@
+extern crate rspirv_cfg;
+extern crate serde_json;
+extern crate spirv_headers as spirv;
+
+use rspirv_cfg::{write_json, write_spirv_cfg, CallGraph, DotOptions, FunctionFilter, SpirvModule};
+use serde_json::{json, Value};
+
+fn op(opcode: spirv::Op, operands: &[u32]) -> Vec<u32> {
+    let mut words = vec![(operands.len() as u32 + 1) << 16 | opcode as u32];
+    words.extend_from_slice(operands);
+    words
+}
+
+/// A function that switches on a 64 bit constant, assembled by hand. Block 6
+/// is the header, 7 and 8 are the case targets that branch to the merge block
+/// 9.
+fn switch64(cases: &[(u64, u32)], default: u32) -> Vec<u32> {
+    let mut switch = vec![4, default];
+    for &(value, target) in cases {
+        switch.extend_from_slice(&[value as u32, (value >> 32) as u32, target]);
+    }
+    let instructions = vec![
+        op(spirv::Op::Capability, &[spirv::Capability::Shader as u32]),
+        op(spirv::Op::Capability, &[spirv::Capability::Int64 as u32]),
+        op(spirv::Op::MemoryModel, &[0, 1]),
+        op(spirv::Op::TypeVoid, &[1]),
+        op(spirv::Op::TypeInt, &[2, 64, 0]),
+        op(spirv::Op::TypeFunction, &[3, 1]),
+        op(spirv::Op::Constant, &[2, 4, 3, 0]),
+        op(spirv::Op::Function, &[1, 5, 0, 3]),
+        op(spirv::Op::Label, &[6]),
+        op(spirv::Op::SelectionMerge, &[9, 0]),
+        op(spirv::Op::Switch, &switch),
+        op(spirv::Op::Label, &[7]),
+        op(spirv::Op::Branch, &[9]),
+        op(spirv::Op::Label, &[8]),
+        op(spirv::Op::Branch, &[9]),
+        op(spirv::Op::Label, &[9]),
+        op(spirv::Op::Return, &[]),
+        op(spirv::Op::FunctionEnd, &[]),
+    ];
+    let mut words = vec![spirv::MAGIC_NUMBER, 0x0001_0000, 0, 10, 0];
+    words.extend(instructions.into_iter().flatten());
+    words
+}
+
+fn dot(bytes: &[u8]) -> String {
+    let module = SpirvModule::from_bytes(bytes).unwrap();
+    let mut dot = Vec::new();
+    write_spirv_cfg(
+        &module,
+        &CallGraph::new(&module),
+        &DotOptions::default(),
+        &mut dot,
+    )
+    .unwrap();
+    String::from_utf8(dot).unwrap()
+}
+
+fn to_bytes(words: &[u32], big_endian: bool) -> Vec<u8> {
+    words
+        .iter()
+        .flat_map(|word| {
+            if big_endian {
+                word.to_be_bytes()
+            } else {
+                word.to_le_bytes()
+            }
+            .to_vec()
+        })
+        .collect()
+}
+
+/// The lines of the DOT output for the edges leaving the switch header.
+fn switch_edges(dot: &str) -> Vec<&str> {
+    dot.lines()
+        .map(str::trim)
+        .filter(|line| line.starts_with("6 -> ") && !line.contains("dashed"))
+        .collect()
+}
+
+#[test]
+fn single_case() {
+    let output = dot(&to_bytes(&switch64(&[(1 << 40, 7)], 8), false));
+    assert_eq!(
+        switch_edges(&output),
+        vec![
+            "6 -> 7[label=\"case 1099511627776\"]",
+            "6 -> 8[style=\"bold\", label=\"default\"]",
+        ]
+    );
+}
+
+#[test]
+fn several_cases() {
+    // An even number of cases needs no padding
+    let output = dot(&to_bytes(&switch64(&[(1, 7), (u64::MAX, 8)], 9), false));
+    assert_eq!(
+        switch_edges(&output),
+        vec![
+            "6 -> 7[label=\"case 1\"]",
+            "6 -> 8[label=\"case 18446744073709551615\"]",
+            "6 -> 9[style=\"bold\", label=\"default\"]",
+        ]
+    );
+
+    let output = dot(&to_bytes(
+        &switch64(&[(1, 7), (2, 8), (3 << 32, 9)], 9),
+        false,
+    ));
+    assert_eq!(
+        switch_edges(&output),
+        vec![
+            "6 -> 7[label=\"case 1\"]",
+            "6 -> 8[label=\"case 2\"]",
+            "6 -> 9[style=\"bold\", label=\"case 12884901888, default\"]",
+        ]
+    );
+}
+
+#[test]
+fn cases_sharing_a_target() {
+    let cases = [(1, 7), (1 << 63, 8), (5, 7)];
+    let output = dot(&to_bytes(&switch64(&cases, 8), false));
+    assert_eq!(
+        switch_edges(&output),
+        vec![
+            "6 -> 7[label=\"case 1, 5\"]",
+            "6 -> 8[style=\"bold\", label=\"case 9223372036854775808, default\"]",
+        ]
+    );
+}
+
+#[test]
+fn big_endian() {
+    let cases = [(1, 7), (1 << 63, 8), (5, 7)];
+    let words = switch64(&cases, 8);
+    assert_eq!(dot(&to_bytes(&words, true)), dot(&to_bytes(&words, false)));
+    let words = switch64(&[(1, 7), (2, 8)], 9);
+    assert_eq!(dot(&to_bytes(&words, true)), dot(&to_bytes(&words, false)));
+}
+
+#[test]
+fn json_case_values_are_strings() {
+    // 2^63 + 1 can't be represented exactly by a JavaScript number
+    let words = switch64(&[((1 << 63) + 1, 7), (2, 8)], 9);
+    let module = SpirvModule::from_bytes(&to_bytes(&words, false)).unwrap();
+    let mut json = Vec::new();
+    write_json(
+        &module,
+        &CallGraph::new(&module),
+        &FunctionFilter::default(),
+        &mut json,
+    )
+    .unwrap();
+    let document: Value = serde_json::from_slice(&json).unwrap();
+    let values: Vec<&Value> = document["functions"][0]["edges"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|edge| edge["kind"] == "case")
+        .map(|edge| &edge["values"])
+        .collect();
+    assert_eq!(
+        values,
+        vec![&json!(["9223372036854775809"]), &json!(["2"]), &json!([])]
+    );
+}